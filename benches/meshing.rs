@@ -0,0 +1,78 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use minecrab::{
+    settings::Settings,
+    world::{
+        block::{Block, BlockType},
+        chunk::{Chunk, CHUNK_SIZE},
+    },
+};
+use noise::{Fbm, NoiseFn};
+
+/// The bottom half of the chunk is stone, the top half air.
+fn flat_chunk() -> Chunk {
+    let mut chunk = Chunk::default();
+    for layer in &mut chunk.blocks[..CHUNK_SIZE / 2] {
+        *layer = [[Some(Block::new(BlockType::Stone)); CHUNK_SIZE]; CHUNK_SIZE];
+    }
+    chunk
+}
+
+/// A chunk of generated terrain at the height of the surface, with hills, grass and trees.
+fn terrain_chunk() -> Chunk {
+    let mut chunk = Chunk::default();
+    chunk.generate(0, 4, 0);
+    chunk
+}
+
+/// A chunk of stone with winding tunnels carved out by 3D noise.
+fn cave_chunk() -> Chunk {
+    let fbm = Fbm::new();
+    let mut chunk = Chunk::default();
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let point = [x as f64 / 12.0, y as f64 / 12.0, z as f64 / 12.0];
+                if fbm.get(point).abs() > 0.15 {
+                    chunk.blocks[y][z][x] = Some(Block::new(BlockType::Stone));
+                }
+            }
+        }
+    }
+    chunk
+}
+
+/// Every other block is stone, so no faces are hidden or merged. This is the worst case.
+fn checkerboard_chunk() -> Chunk {
+    let mut chunk = Chunk::default();
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if (x + y + z) % 2 == 0 {
+                    chunk.blocks[y][z][x] = Some(Block::new(BlockType::Stone));
+                }
+            }
+        }
+    }
+    chunk
+}
+
+fn meshing(c: &mut Criterion) {
+    let settings = Settings::default();
+    let chunks = [
+        ("flat", flat_chunk()),
+        ("terrain", terrain_chunk()),
+        ("caves", cave_chunk()),
+        ("checkerboard", checkerboard_chunk()),
+    ];
+
+    let mut group = c.benchmark_group("mesh_isolated");
+    for (name, chunk) in &chunks {
+        group.bench_function(*name, |b| {
+            b.iter(|| black_box(chunk).mesh_isolated(&settings))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, meshing);
+criterion_main!(benches);
@@ -1,4 +1,4 @@
-use cgmath::Point3;
+use cgmath::{Point3, Vector3};
 
 #[derive(Debug)]
 pub struct Aabb {
@@ -13,33 +13,35 @@ impl Aabb {
             && (self.min.z <= other.max.z && self.max.z >= other.min.z)
     }
 
-    /// Gets the corners of the AABB that should be checked when checking
-    /// collision with the world.
-    ///
-    /// Returns a `Vec` of all `Point3`s that cover the faces of `self` with
-    /// no more than 1 unit of distance between them.
-    pub fn get_corners(&self) -> Vec<Point3<f32>> {
-        let mut corners = Vec::new();
+    /// Like `intersects`, but boxes that only touch each other don't count as overlapping.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        (self.min.x < other.max.x && self.max.x > other.min.x)
+            && (self.min.y < other.max.y && self.max.y > other.min.y)
+            && (self.min.z < other.max.z && self.max.z > other.min.z)
+    }
 
-        let mut x = self.min.x;
-        while x < self.max.x.ceil() {
-            let mut y = self.min.y;
-            while y < self.max.y.ceil() {
-                let mut z = self.min.z;
-                while z < self.max.z.ceil() {
-                    corners.push(Point3::new(
-                        x.min(self.max.x),
-                        y.min(self.max.y),
-                        z.min(self.max.z),
-                    ));
-                    z += 1.0;
-                }
-                y += 1.0;
-            }
-            x += 1.0;
+    /// Returns the AABB moved by `offset`.
+    pub fn translate(&self, offset: Vector3<f32>) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
         }
+    }
 
-        corners
+    /// Returns the smallest AABB containing both `self` and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
     }
 }
 
@@ -0,0 +1,235 @@
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use wgpu::{Buffer, BufferAddress, BufferUsage};
+
+use crate::render_context::RenderContext;
+
+/// A range of a `BufferArena`, which is freed when dropped.
+///
+/// The range can move when the arena is defragmented, so it has to be looked up with
+/// `BufferArena::range` whenever it's used.
+pub struct Allocation {
+    id: usize,
+    /// The size of the data in the allocation, in bytes.
+    pub size: BufferAddress,
+    /// The arena's list of dropped allocations.
+    released: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.released.lock().unwrap().push(self.id);
+    }
+}
+
+/// A single large GPU buffer that many small pieces of data are allocated from, so they can be
+/// drawn without switching buffers.
+///
+/// Free space is tracked in a free list and allocated first fit. When no free range is large
+/// enough, the allocations are moved to a new buffer without any gaps between them, which also
+/// grows the buffer if there isn't enough free space in total.
+pub struct BufferArena {
+    label: &'static str,
+    usage: BufferUsage,
+    /// The alignment of every allocation, in bytes. Has to be a multiple of
+    /// `wgpu::COPY_BUFFER_ALIGNMENT`.
+    alignment: BufferAddress,
+    pub buffer: Buffer,
+    /// The size of the buffer, in bytes.
+    pub capacity: BufferAddress,
+    /// The free ranges of the buffer, sorted by their offset. Neighbouring free ranges are always
+    /// merged.
+    free: Vec<Range<BufferAddress>>,
+    /// The range of every allocation, by id. `None` for ids that aren't in use.
+    ranges: Vec<Option<Range<BufferAddress>>>,
+    /// The ids of dropped allocations, which are freed with the next allocation.
+    released: Arc<Mutex<Vec<usize>>>,
+}
+
+impl BufferArena {
+    pub fn new(
+        render_context: &RenderContext,
+        label: &'static str,
+        usage: BufferUsage,
+        alignment: BufferAddress,
+        capacity: BufferAddress,
+    ) -> Self {
+        assert_eq!(alignment % wgpu::COPY_BUFFER_ALIGNMENT, 0);
+        let capacity = Self::align(capacity, alignment);
+        let usage = usage | BufferUsage::COPY_DST | BufferUsage::COPY_SRC;
+
+        Self {
+            label,
+            usage,
+            alignment,
+            buffer: Self::create_buffer(render_context, label, usage, capacity),
+            capacity,
+            free: std::iter::once(0..capacity).collect(),
+            ranges: Vec::new(),
+            released: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn create_buffer(
+        render_context: &RenderContext,
+        label: &str,
+        usage: BufferUsage,
+        size: BufferAddress,
+    ) -> Buffer {
+        render_context
+            .device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+    }
+
+    fn align(size: BufferAddress, alignment: BufferAddress) -> BufferAddress {
+        size + (alignment - size % alignment) % alignment
+    }
+
+    /// Returns the current range of the allocation in the buffer, in bytes. The range is aligned,
+    /// so it may be larger than the data in it.
+    pub fn range(&self, allocation: &Allocation) -> Range<BufferAddress> {
+        self.ranges[allocation.id].clone().unwrap()
+    }
+
+    /// Returns the number of bytes in use by allocations.
+    pub fn used(&self) -> BufferAddress {
+        self.capacity
+            - self
+                .free
+                .iter()
+                .map(|range| range.end - range.start)
+                .sum::<u64>()
+    }
+
+    /// Allocates a range of the buffer and writes `data` to it.
+    pub fn allocate(&mut self, render_context: &RenderContext, data: &[u8]) -> Allocation {
+        self.free_released();
+
+        let size = Self::align(data.len() as BufferAddress, self.alignment).max(self.alignment);
+        let offset = match self.find_free(size) {
+            Some(offset) => offset,
+            None => {
+                // Either the free space is too fragmented, or there's too little of it
+                let needed = self.used() + size;
+                let mut capacity = self.capacity;
+                while capacity < needed {
+                    capacity *= 2;
+                }
+                self.defragment(render_context, capacity);
+                self.find_free(size).unwrap()
+            }
+        };
+
+        let id = match self.ranges.iter().position(Option::is_none) {
+            Some(id) => id,
+            None => {
+                self.ranges.push(None);
+                self.ranges.len() - 1
+            }
+        };
+        self.ranges[id] = Some(offset..offset + size);
+
+        // Writes have to be a multiple of 4 bytes long, which the aligned size always is
+        if data.len() as BufferAddress == size {
+            render_context
+                .queue
+                .write_buffer(&self.buffer, offset, data);
+        } else {
+            let mut padded = data.to_vec();
+            padded.resize(size as usize, 0);
+            render_context
+                .queue
+                .write_buffer(&self.buffer, offset, &padded);
+        }
+
+        Allocation {
+            id,
+            size: data.len() as BufferAddress,
+            released: self.released.clone(),
+        }
+    }
+
+    /// Takes a range of `size` bytes from the first free range it fits in, and returns its
+    /// offset.
+    fn find_free(&mut self, size: BufferAddress) -> Option<BufferAddress> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= size)?;
+        let offset = self.free[index].start;
+        self.free[index].start += size;
+        if self.free[index].start == self.free[index].end {
+            self.free.remove(index);
+        }
+        Some(offset)
+    }
+
+    /// Returns the ranges of the dropped allocations to the free list.
+    fn free_released(&mut self) {
+        let released = std::mem::take(&mut *self.released.lock().unwrap());
+        for id in released {
+            let range = self.ranges[id].take().unwrap();
+            let index = self
+                .free
+                .iter()
+                .position(|free| free.start > range.start)
+                .unwrap_or(self.free.len());
+            self.free.insert(index, range);
+
+            // Merge with the free ranges on either side
+            if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+                self.free[index].end = self.free.remove(index + 1).end;
+            }
+            if index > 0 && self.free[index - 1].end == self.free[index].start {
+                self.free[index - 1].end = self.free.remove(index).end;
+            }
+        }
+    }
+
+    /// Moves all allocations to the start of a new buffer of `capacity` bytes, in the order they
+    /// are in now, leaving a single free range at its end.
+    fn defragment(&mut self, render_context: &RenderContext, capacity: BufferAddress) {
+        let buffer = Self::create_buffer(render_context, self.label, self.usage, capacity);
+        let mut encoder =
+            render_context
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("buffer_arena_defragment_encoder"),
+                });
+
+        let mut allocations: Vec<_> = self
+            .ranges
+            .iter_mut()
+            .filter_map(|range| range.as_mut())
+            .collect();
+        allocations.sort_unstable_by_key(|range| range.start);
+
+        let mut offset = 0;
+        for range in allocations {
+            let size = range.end - range.start;
+            encoder.copy_buffer_to_buffer(&self.buffer, range.start, &buffer, offset, size);
+            *range = offset..offset + size;
+            offset += size;
+        }
+
+        // Data written to the old buffer earlier is written before the copies are made
+        render_context
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        self.buffer = buffer;
+        self.capacity = capacity;
+        self.free.clear();
+        if offset < capacity {
+            self.free.push(offset..capacity);
+        }
+    }
+}
@@ -8,6 +8,17 @@ pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
+/// Reverses the depth of wgpu's clip space, so the near plane ends up at a depth of 1 and the far
+/// plane at 0. Floating point depths are most precise close to 0, which makes up for the
+/// perspective divide squeezing distant geometry together, so far terrain doesn't z-fight.
+#[rustfmt::skip]
+pub const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0,  0.0, 0.0,
+    0.0, 1.0,  0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0,  1.0, 1.0,
+);
+
 pub struct Camera {
     pub position: Point3<f32>,
     pub yaw: Rad<f32>,
@@ -63,8 +74,10 @@ impl Projection {
         self.aspect_ratio = width as f32 / height as f32;
     }
 
+    /// Returns the projection matrix, with reversed depth (see `REVERSE_Z_MATRIX`).
     pub fn calculate_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX
+        REVERSE_Z_MATRIX
+            * OPENGL_TO_WGPU_MATRIX
             * cgmath::perspective(self.fov_y, self.aspect_ratio, self.z_near, self.z_far)
     }
 }
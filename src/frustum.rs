@@ -0,0 +1,94 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector4};
+
+use crate::aabb::Aabb;
+
+/// The six planes bounding the part of the world a view projection matrix projects onto the
+/// screen.
+pub struct Frustum {
+    /// The planes as `(normal, distance)`, with their normals pointing into the frustum.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of the frustum of a view projection matrix, which projects into the
+    /// clip space of wgpu, i.e. with depths from 0 to 1.
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Self {
+        let (x, y, z, w) = (matrix.row(0), matrix.row(1), matrix.row(2), matrix.row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z];
+
+        Self {
+            planes: [
+                Self::normalize(planes[0]),
+                Self::normalize(planes[1]),
+                Self::normalize(planes[2]),
+                Self::normalize(planes[3]),
+                Self::normalize(planes[4]),
+                Self::normalize(planes[5]),
+            ],
+        }
+    }
+
+    fn normalize(plane: Vector4<f32>) -> Vector4<f32> {
+        plane / plane.truncate().magnitude()
+    }
+
+    /// Returns whether the box is at least partially inside the frustum.
+    ///
+    /// Boxes that are outside, but near one of the edges of the frustum, may be counted as
+    /// inside, as they're on the inner side of every plane.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        // The corner of the box the furthest along the normal of a plane
+        let furthest = |normal: f32, min: f32, max: f32| if normal >= 0.0 { max } else { min };
+        self.planes.iter().all(|plane| {
+            let x = furthest(plane.x, aabb.min.x, aabb.max.x);
+            let y = furthest(plane.y, aabb.min.y, aabb.max.y);
+            let z = furthest(plane.z, aabb.min.z, aabb.max.z);
+            plane.x * x + plane.y * y + plane.z * z + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Projection, OPENGL_TO_WGPU_MATRIX};
+    use cgmath::{Deg, Point3};
+
+    /// A frustum looking from the origin along the negative Z axis, 10 blocks deep.
+    fn frustum() -> Frustum {
+        let projection = cgmath::perspective(Deg(90.0), 1.0, 0.1, 10.0);
+        Frustum::from_matrix(OPENGL_TO_WGPU_MATRIX * projection)
+    }
+
+    fn unit_box(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb {
+            min: Point3::new(x, y, z),
+            max: Point3::new(x + 1.0, y + 1.0, z + 1.0),
+        }
+    }
+
+    #[test]
+    fn boxes_in_front_are_inside() {
+        let frustum = frustum();
+        assert!(frustum.intersects(&unit_box(-0.5, -0.5, -5.0)));
+        assert!(frustum.intersects(&unit_box(-6.0, 3.0, -8.0)));
+    }
+
+    #[test]
+    fn boxes_behind_beside_and_beyond_are_outside() {
+        let frustum = frustum();
+        assert!(!frustum.intersects(&unit_box(-0.5, -0.5, 1.0)));
+        assert!(!frustum.intersects(&unit_box(-4.0, -0.5, -2.0)));
+        assert!(!frustum.intersects(&unit_box(-0.5, 3.0, -2.0)));
+        assert!(!frustum.intersects(&unit_box(-0.5, -0.5, -12.0)));
+    }
+
+    #[test]
+    fn reversed_depth_keeps_the_planes() {
+        let projection = Projection::new(1, 1, Deg(90.0), 0.1, 10.0);
+        let frustum = Frustum::from_matrix(projection.calculate_matrix());
+        assert!(frustum.intersects(&unit_box(-0.5, -0.5, -5.0)));
+        assert!(!frustum.intersects(&unit_box(-0.5, -0.5, 1.0)));
+        assert!(!frustum.intersects(&unit_box(-0.5, -0.5, -12.0)));
+    }
+}
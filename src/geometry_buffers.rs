@@ -11,6 +11,8 @@ pub struct GeometryBuffers<I> {
     pub vertices: wgpu::Buffer,
     pub indices: wgpu::Buffer,
     pub index_count: usize,
+    /// The combined size of the vertex and index buffers, in bytes.
+    pub size: wgpu::BufferAddress,
 
     // Phantom data to store the index type
     _phantom: PhantomData<I>,
@@ -22,11 +24,14 @@ impl<I: bytemuck::Pod> GeometryBuffers<I> {
         geometry: &Geometry<V, I>,
         usage: wgpu::BufferUsage,
     ) -> Self {
+        let vertex_data: &[u8] = bytemuck::cast_slice(&geometry.vertices);
+        let index_data: &[u8] = bytemuck::cast_slice(&geometry.indices);
+
         let vertices = render_context
             .device
             .create_buffer_init(&BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&geometry.vertices),
+                contents: vertex_data,
                 usage: wgpu::BufferUsage::VERTEX | usage,
             });
 
@@ -34,7 +39,7 @@ impl<I: bytemuck::Pod> GeometryBuffers<I> {
             .device
             .create_buffer_init(&BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&geometry.indices),
+                contents: index_data,
                 usage: wgpu::BufferUsage::INDEX | usage,
             });
 
@@ -42,6 +47,7 @@ impl<I: bytemuck::Pod> GeometryBuffers<I> {
             vertices,
             indices,
             index_count: geometry.index_count(),
+            size: (vertex_data.len() + index_data.len()) as wgpu::BufferAddress,
             _phantom: PhantomData,
         }
     }
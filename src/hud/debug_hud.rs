@@ -7,6 +7,7 @@ use crate::{
     geometry_buffers::GeometryBuffers,
     render_context::RenderContext,
     text_renderer::{self, TextRenderer},
+    world::World,
 };
 
 pub struct DebugHud {
@@ -19,6 +20,16 @@ pub struct DebugHud {
 
     coordinates_last: Point3<f32>,
     coordinates_geometry_buffers: GeometryBuffers<u16>,
+
+    /// `World::chunk_uploaded_bytes` when the memory text was last updated.
+    memory_uploaded_bytes: u64,
+    memory_geometry_buffers: GeometryBuffers<u16>,
+
+    culling_geometry_buffers: GeometryBuffers<u16>,
+
+    /// `Settings::anisotropy` when the settings text was last updated.
+    settings_anisotropy: Option<u8>,
+    settings_geometry_buffers: GeometryBuffers<u16>,
 }
 
 impl DebugHud {
@@ -28,6 +39,24 @@ impl DebugHud {
             text_renderer.string_to_buffers(&render_context, -0.98, 0.97, "");
         let coordinates_geometry_buffers =
             text_renderer.string_to_buffers(&render_context, -0.98, 0.97 - text_renderer::DY, "");
+        let memory_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * 2.6,
+            "",
+        );
+        let culling_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * 3.6,
+            "",
+        );
+        let settings_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * 4.6,
+            "",
+        );
 
         Self {
             text_renderer,
@@ -39,10 +68,23 @@ impl DebugHud {
 
             coordinates_last: Point3::new(0.0, 0.0, 0.0),
             coordinates_geometry_buffers,
+
+            memory_uploaded_bytes: 0,
+            memory_geometry_buffers,
+
+            culling_geometry_buffers,
+
+            settings_anisotropy: None,
+            settings_geometry_buffers,
         }
     }
 
-    pub fn update(&mut self, render_context: &RenderContext, position: &Point3<f32>) {
+    pub fn update(
+        &mut self,
+        render_context: &RenderContext,
+        position: &Point3<f32>,
+        world: &World,
+    ) {
         let elapsed = self.fps_instant.elapsed();
         self.fps_instant = Instant::now();
         self.fps_elapsed += elapsed;
@@ -57,6 +99,44 @@ impl DebugHud {
                 self.text_renderer
                     .string_to_buffers(render_context, -0.98, 0.97, &string);
 
+            // The chunk memory is updated at the same rate, to measure the uploads over the period
+            let uploaded = world.chunk_uploaded_bytes - self.memory_uploaded_bytes;
+            let (arena_used, arena_capacity) = world.chunk_arena.usage();
+            let string = format!(
+                "chunks: {:.1} MiB ({:.0}/{:.0} MiB of arena), uploading {:.0} KiB/s, {}",
+                world.chunk_buffer_size() as f32 / (1024.0 * 1024.0),
+                arena_used as f32 / (1024.0 * 1024.0),
+                arena_capacity as f32 / (1024.0 * 1024.0),
+                uploaded as f32 / 1024.0 / self.fps_elapsed.as_secs_f32(),
+                if world.chunk_arena.multi_draw() {
+                    "multi-draw"
+                } else {
+                    "draw per mesh"
+                },
+            );
+            self.memory_geometry_buffers = self.text_renderer.string_to_buffers(
+                render_context,
+                -0.98,
+                0.97 - text_renderer::DY * 2.6,
+                &string,
+            );
+            self.memory_uploaded_bytes = world.chunk_uploaded_bytes;
+
+            let stats = world.render_stats;
+            let string = format!(
+                "drawn: {} chunks, {} entities, culled: {} chunks, {} entities",
+                stats.chunks_drawn,
+                stats.entities_drawn,
+                stats.chunks_culled,
+                stats.entities_culled,
+            );
+            self.culling_geometry_buffers = self.text_renderer.string_to_buffers(
+                render_context,
+                -0.98,
+                0.97 - text_renderer::DY * 3.6,
+                &string,
+            );
+
             self.fps_elapsed = Duration::from_secs(0);
             self.fps_frames = 0;
         }
@@ -70,6 +150,18 @@ impl DebugHud {
                 &string,
             );
         }
+
+        let anisotropy = render_context.settings.anisotropy;
+        if self.settings_anisotropy != Some(anisotropy) {
+            let string = format!("anisotropic filtering: {}x", anisotropy);
+            self.settings_geometry_buffers = self.text_renderer.string_to_buffers(
+                render_context,
+                -0.98,
+                0.97 - text_renderer::DY * 4.6,
+                &string,
+            );
+            self.settings_anisotropy = Some(anisotropy);
+        }
     }
 
     pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
@@ -85,6 +177,21 @@ impl DebugHud {
         render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
         triangle_count += self.coordinates_geometry_buffers.draw_indexed(render_pass);
 
+        // Render the chunk memory text
+        self.memory_geometry_buffers.apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self.memory_geometry_buffers.draw_indexed(render_pass);
+
+        // Render the culling text
+        self.culling_geometry_buffers.apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self.culling_geometry_buffers.draw_indexed(render_pass);
+
+        // Render the settings text
+        self.settings_geometry_buffers.apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self.settings_geometry_buffers.draw_indexed(render_pass);
+
         triangle_count
     }
 }
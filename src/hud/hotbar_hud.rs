@@ -10,6 +10,27 @@ use crate::{
     world::block::BlockType,
 };
 
+/// The blocks the slots of the hotbar can be switched between, in order.
+const PICKABLE_BLOCKS: &[BlockType] = &[
+    BlockType::Dirt,
+    BlockType::Stone,
+    BlockType::Sand,
+    BlockType::Gravel,
+    BlockType::Grass,
+    BlockType::Cobblestone,
+    BlockType::OakPlanks,
+    BlockType::OakLog,
+    BlockType::OakLeaves,
+    BlockType::StoneSlab,
+    BlockType::OakSlab,
+    BlockType::CobblestoneStairs,
+    BlockType::OakStairs,
+    BlockType::TallGrass,
+    BlockType::Poppy,
+    BlockType::Torch,
+    BlockType::Glowstone,
+];
+
 pub struct HotbarHud {
     pub blocks: [Option<BlockType>; 9],
     pub last_blocks: [Option<BlockType>; 9],
@@ -23,7 +44,7 @@ impl HotbarHud {
             Some(BlockType::Dirt),
             Some(BlockType::Stone),
             Some(BlockType::Sand),
-            None,
+            Some(BlockType::OakSlab),
             Some(BlockType::Grass),
             Some(BlockType::Cobblestone),
             Some(BlockType::OakPlanks),
@@ -53,6 +74,21 @@ impl HotbarHud {
         }
     }
 
+    /// Switches the block in the slot to the one `delta` places further in `PICKABLE_BLOCKS`.
+    pub fn cycle_block(&mut self, slot: usize, delta: isize) {
+        let count = PICKABLE_BLOCKS.len() as isize;
+        let index = match self.blocks[slot] {
+            Some(block) => PICKABLE_BLOCKS
+                .iter()
+                .position(|&pickable| pickable == block)
+                .map_or(0, |index| index as isize + delta),
+            // An empty slot starts from either end of the list
+            None if delta > 0 => delta - 1,
+            None => count + delta,
+        };
+        self.blocks[slot] = Some(PICKABLE_BLOCKS[index.rem_euclid(count) as usize]);
+    }
+
     pub fn render<'a>(
         &'a self,
         render_context: &'a RenderContext,
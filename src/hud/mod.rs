@@ -118,8 +118,10 @@ impl Hud {
         &mut self,
         render_context: &crate::render_context::RenderContext,
         camera: &crate::camera::Camera,
+        world: &crate::world::World,
     ) {
-        self.debug_hud.update(render_context, &camera.position);
+        self.debug_hud
+            .update(render_context, &camera.position, world);
         self.hotbar_hud.update(render_context);
     }
 
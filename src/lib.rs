@@ -0,0 +1,20 @@
+mod aabb;
+mod buffer_arena;
+mod camera;
+mod frustum;
+mod geometry;
+mod geometry_buffers;
+mod hud;
+mod player;
+mod render_context;
+pub mod settings;
+mod state;
+mod text_renderer;
+mod texture;
+mod time;
+mod utils;
+mod vertex;
+mod view;
+pub mod world;
+
+pub use state::State;
@@ -1,19 +1,3 @@
-mod aabb;
-mod camera;
-mod geometry;
-mod geometry_buffers;
-mod hud;
-mod player;
-mod render_context;
-mod state;
-mod text_renderer;
-mod texture;
-mod time;
-mod utils;
-mod vertex;
-mod view;
-mod world;
-
 use std::time::{Duration, Instant};
 use wgpu::SwapChainError;
 use winit::{
@@ -23,7 +7,7 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
-use crate::state::State;
+use minecrab::State;
 
 fn handle_window_event(
     event: &WindowEvent,
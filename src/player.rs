@@ -1,18 +1,26 @@
 use std::time::Duration;
 
-use cgmath::{InnerSpace, Point3, Rad, Vector3};
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Rad, Vector3};
 
-use crate::{aabb::Aabb, render_context::RenderContext, utils, view::View, world::World};
+use crate::{
+    aabb::Aabb,
+    render_context::RenderContext,
+    utils,
+    view::View,
+    world::{block::BlockType, World},
+};
 
 pub struct Player {
     pub sprinting: bool,
     pub grounded: bool,
+    pub swimming: bool,
     pub creative: bool,
 
     pub forward_pressed: bool,
     pub backward_pressed: bool,
     pub left_pressed: bool,
     pub right_pressed: bool,
+    pub break_pressed: bool,
     pub up_speed: f32,
 
     pub view: View,
@@ -25,12 +33,14 @@ impl Player {
         Self {
             sprinting: false,
             grounded: false,
+            swimming: false,
             creative: false,
 
             forward_pressed: false,
             backward_pressed: false,
             left_pressed: false,
             right_pressed: false,
+            break_pressed: false,
             up_speed: 0.0,
 
             view,
@@ -69,6 +79,17 @@ impl Player {
             velocity = velocity.normalize();
         }
         velocity *= speed;
+
+        // The player is swimming when the middle of their body is in water
+        let body = self.view.camera.position - Vector3::new(0.0, 0.8, 0.0);
+        self.swimming = world
+            .get_block(body.map(|x| x.floor() as isize))
+            .map(|block| block.block_type)
+            == Some(BlockType::Water);
+        if self.swimming {
+            velocity *= 0.5;
+        }
+
         velocity.y = self.up_speed * 10.0 * dt.as_secs_f32();
 
         let mut new_position = self.view.camera.position;
@@ -78,10 +99,10 @@ impl Player {
             new_position.y += velocity.y;
             if let Some(aabb) = self.check_collision(new_position, world) {
                 if self.up_speed < 0.0 {
-                    new_position.y = aabb.min.y.ceil() + 1.62;
+                    new_position.y = aabb.max.y + 1.62;
                     new_position.y = utils::f32_successor(new_position.y);
                 } else if self.up_speed > 0.0 {
-                    new_position.y = aabb.max.y.floor() - 0.18;
+                    new_position.y = aabb.min.y - 0.18;
                     new_position.y = utils::f32_predecessor(new_position.y);
                 }
 
@@ -95,10 +116,10 @@ impl Player {
             new_position.x += velocity.x;
             if let Some(aabb) = self.check_collision(new_position, world) {
                 if velocity.x < 0.0 {
-                    new_position.x = aabb.min.x.ceil() + 0.3;
+                    new_position.x = aabb.max.x + 0.3;
                     new_position.x = utils::f32_successor(new_position.x);
                 } else if velocity.x > 0.0 {
-                    new_position.x = aabb.max.x.floor() - 0.3;
+                    new_position.x = aabb.min.x - 0.3;
                     new_position.x = utils::f32_predecessor(new_position.x);
                 }
             }
@@ -107,35 +128,63 @@ impl Player {
             new_position.z += velocity.z;
             if let Some(aabb) = self.check_collision(new_position, world) {
                 if velocity.z < 0.0 {
-                    new_position.z = aabb.min.z.ceil() + 0.3;
+                    new_position.z = aabb.max.z + 0.3;
                     new_position.z = utils::f32_successor(new_position.z);
                 } else if velocity.z > 0.0 {
-                    new_position.z = aabb.max.z.floor() - 0.3;
+                    new_position.z = aabb.min.z - 0.3;
                     new_position.z = utils::f32_predecessor(new_position.z);
                 }
             }
 
-            self.up_speed -= 1.6 * dt.as_secs_f32();
-            self.up_speed *= 0.98_f32.powf(dt.as_secs_f32() / 20.0);
+            if self.swimming {
+                // Sink slowly in water
+                self.up_speed -= 0.4 * dt.as_secs_f32();
+                self.up_speed = self.up_speed.max(-0.3);
+            } else {
+                self.up_speed -= 1.6 * dt.as_secs_f32();
+                self.up_speed *= 0.98_f32.powf(dt.as_secs_f32() / 20.0);
+            }
         } else {
             new_position += velocity;
         }
         self.view.camera.position = new_position;
     }
 
+    /// Checks whether the player would collide with any blocks at `position`.
+    ///
+    /// Returns the bounding box of all block boxes the player's bounding box overlaps with.
     fn check_collision(&self, position: Point3<f32>, world: &World) -> Option<Aabb> {
         let aabb = Aabb {
             min: position + Vector3::new(-0.3, -1.62, -0.3),
             max: position + Vector3::new(0.3, 0.18, 0.3),
         };
 
-        for corner in &aabb.get_corners() {
-            let block = world.get_block(corner.map(|x| x.floor() as isize));
-            if block.is_some() {
-                return Some(aabb);
+        let min = aabb.min.map(|x| x.floor() as isize);
+        let max = aabb.max.map(|x| x.floor() as isize);
+
+        let mut collision: Option<Aabb> = None;
+        for (x, y, z) in itertools::iproduct!(min.x..=max.x, min.y..=max.y, min.z..=max.z) {
+            let block_position = Point3::new(x, y, z);
+            if let Some(block) = world.get_block(block_position) {
+                if !block.block_type.is_solid() {
+                    continue;
+                }
+
+                let offset = block_position.cast().unwrap().to_vec();
+                for block_aabb in block.block_type.shape().boxes(block.facing) {
+                    let block_aabb = block_aabb.translate(offset);
+                    if !block_aabb.overlaps(&aabb) {
+                        continue;
+                    }
+
+                    collision = Some(match collision {
+                        Some(collision) => collision.union(&block_aabb),
+                        None => block_aabb,
+                    });
+                }
             }
         }
 
-        None
+        collision
     }
 }
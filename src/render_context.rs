@@ -1,4 +1,4 @@
-use crate::texture::TextureManager;
+use crate::{settings::Settings, texture::TextureManager};
 
 pub struct RenderContext {
     pub surface: wgpu::Surface,
@@ -7,4 +7,5 @@ pub struct RenderContext {
     pub swap_chain_descriptor: wgpu::SwapChainDescriptor,
     pub swap_chain: wgpu::SwapChain,
     pub texture_manager: Option<TextureManager>,
+    pub settings: Settings,
 }
@@ -0,0 +1,19 @@
+/// Graphics settings that can be changed while the game is running.
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    /// Whether leaves are rendered with see-through holes and show the leaf faces behind them
+    /// ("fancy"), or as opaque cubes that hide each other ("fast").
+    pub fancy_leaves: bool,
+    /// The maximum number of samples anisotropic filtering takes of block textures seen at an
+    /// angle, a power of two from 1 (off) to 16.
+    pub anisotropy: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fancy_leaves: true,
+            anisotropy: 16,
+        }
+    }
+}
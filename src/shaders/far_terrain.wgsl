@@ -0,0 +1,30 @@
+[[block]]
+struct View {
+    position: vec4<f32>;
+    projection: mat4x4<f32>;
+};
+
+// Group 0 is left to the texture array of the world pipelines, so their bind groups stay valid
+[[group(1), binding(0)]]
+var<uniform> view: View;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn main(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] color: vec3<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view.projection * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,18 @@
+[[block]]
+struct View {
+    position: vec4<f32>;
+    projection: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+[[stage(vertex)]]
+fn main([[location(0)]] position: vec3<f32>) -> [[builtin(position)]] vec4<f32> {
+    return view.projection * vec4<f32>(position, 1.0);
+}
+
+[[stage(fragment)]]
+fn main() -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 0.4);
+}
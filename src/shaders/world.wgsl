@@ -19,9 +19,10 @@ struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] texture_coordinates: vec2<f32>;
     [[location(2)]] normal: vec3<f32>;
-    [[location(3)]] highlighted: i32;
-    [[location(4)]] texture_id: i32;
-    [[location(5)]] color: vec4<f32>;
+    [[location(3)]] texture_id: i32;
+    [[location(4)]] color: vec4<f32>;
+    [[location(5)]] light: vec2<f32>;
+    [[location(6)]] ambient_occlusion: f32;
 };
 
 struct VertexOutput {
@@ -29,15 +30,13 @@ struct VertexOutput {
     [[location(0)]] texture_coordinates: vec2<f32>;
     [[location(1)]] world_normal: vec3<f32>;
     [[location(2)]] world_position: vec3<f32>;
-    [[location(3)]] highlighted: i32;
-    [[location(4)]] texture_id: i32;
-    [[location(5)]] color: vec4<f32>;
+    [[location(3)]] texture_id: i32;
+    [[location(4)]] color: vec4<f32>;
+    [[location(5)]] light: vec2<f32>;
+    [[location(6)]] ambient_occlusion: f32;
 };
 
-let pi: f32 = 3.14159265359;
-
-[[stage(vertex)]]
-fn main(model: VertexInput) -> VertexOutput {
+fn vertex_output(model: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
     out.world_normal = model.normal;
@@ -54,43 +53,108 @@ fn main(model: VertexInput) -> VertexOutput {
     }
 
     out.clip_position = view.projection * vec4<f32>(out.world_position, 1.0);
-    out.highlighted = model.highlighted;
     out.color = model.color;
+    out.light = model.light;
+    out.ambient_occlusion = model.ambient_occlusion;
     return out;
 }
 
+[[stage(vertex)]]
+fn main(model: VertexInput) -> VertexOutput {
+    return vertex_output(model);
+}
+
+// The normals of the faces of a `ChunkVertex`, in the same order as `NEIGHBOURS`
+fn face_normal(face: u32) -> vec3<f32> {
+    if (face == 0u) {
+        return vec3<f32>(-1.0, 0.0, 0.0);
+    } elseif (face == 1u) {
+        return vec3<f32>(1.0, 0.0, 0.0);
+    } elseif (face == 2u) {
+        return vec3<f32>(0.0, 0.0, -1.0);
+    } elseif (face == 3u) {
+        return vec3<f32>(0.0, 0.0, 1.0);
+    } elseif (face == 4u) {
+        return vec3<f32>(0.0, -1.0, 0.0);
+    }
+    return vec3<f32>(0.0, 1.0, 0.0);
+}
+
+// `tint(index: u32) -> vec4<f32>`, which returns the color of `TINTS` with the index, is
+// generated by `ChunkVertex::tint_wgsl` and prepended to this file
+
+// Decodes a `ChunkVertex`, placing it in the world with the offset of its chunk from the
+// `ChunkInstance` of the draw
+[[stage(vertex)]]
+fn main_chunk(
+    [[location(0)]] position: u32,
+    [[location(1)]] texture_coordinates: u32,
+    [[location(2)]] material: u32,
+    [[location(3)]] chunk_offset: vec3<f32>,
+) -> VertexOutput {
+    var model: VertexInput;
+
+    let local_position = vec3<f32>(
+        f32(position & 1023u),
+        f32((position >> 10u) & 1023u),
+        f32((position >> 20u) & 1023u),
+    ) / 16.0;
+    model.position = chunk_offset + local_position;
+    model.texture_coordinates = vec2<f32>(
+        f32(texture_coordinates & 1023u),
+        f32((texture_coordinates >> 10u) & 1023u),
+    ) / 16.0;
+    model.normal = face_normal((texture_coordinates >> 20u) & 7u);
+    model.color = tint((texture_coordinates >> 23u) & 3u);
+
+    model.texture_id = i32(material & 255u);
+    model.light = vec2<f32>(
+        f32((material >> 8u) & 15u),
+        f32((material >> 12u) & 15u),
+    ) / 15.0;
+    model.ambient_occlusion = f32((material >> 16u) & 255u) / 255.0;
+
+    return vertex_output(model);
+}
+
 [[group(0), binding(0)]] var texture_sampler: sampler;
 [[group(0), binding(1)]] var texture_array: texture_2d_array<f32>;
 
-[[stage(fragment)]]
-fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let object_color: vec4<f32> = textureSample(
+fn sample_object_color(in: VertexOutput) -> vec4<f32> {
+    return textureSample(
         texture_array,
         texture_sampler,
         in.texture_coordinates,
         in.texture_id
     ) * in.color;
+}
 
-    let light_position = vec3<f32>(-100.0, 500.0, -200.0);
-    let light_color = vec3<f32>(1.0, 1.0, 1.0);
-
-    let ambient_strength = 0.1;
-    let ambient_color = light_color * ambient_strength;
+fn shade(in: VertexOutput, object_color: vec4<f32>) -> vec4<f32> {
+    // Each light level is 80% as bright as the one above it, like in Minecraft
+    let light_level = max(in.light.x, in.light.y) * 15.0;
+    let brightness = max(pow(0.8, 15.0 - light_level), 0.05);
 
-    let light_direction = normalize(light_position - in.world_position);
-    let view_direction = normalize(view.position.xyz - in.world_position);
-    let half_direction = normalize(view_direction + light_direction);
+    // Shade the sides of blocks differently, so edges between them are visible
+    let normal = in.world_normal;
+    let face_shade = abs(normal.x) * 0.6 + abs(normal.z) * 0.8
+        + max(normal.y, 0.0) + max(-normal.y, 0.0) * 0.5;
 
-    let diffuse_strength = max(dot(in.world_normal, light_direction), 0.0);
-    let diffuse_color = light_color * diffuse_strength;
+    let result = brightness * face_shade * in.ambient_occlusion * object_color.xyz;
+    return vec4<f32>(result, object_color.a);
+}
 
-    let specular_strength = pow(max(dot(in.world_normal, half_direction), 0.0), 32.0);
-    let specular_color = specular_strength * light_color;
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return shade(in, sample_object_color(in));
+}
 
-    var result: vec3<f32> = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
-    if (in.highlighted != 0) {
-        result = result + 0.25 + sin(time.time * pi) * 0.07;
+// Used for geometry with fully transparent holes, e.g. leaves
+[[stage(fragment)]]
+fn main_cutout(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let object_color = sample_object_color(in);
+    if (object_color.a < 0.5) {
+        discard;
     }
 
-    return vec4<f32>(result, object_color.a);
+    return shade(in, object_color);
 }
@@ -12,6 +12,7 @@ use crate::{
     hud::Hud,
     player::Player,
     render_context::RenderContext,
+    settings::Settings,
     texture::{Texture, TextureManager},
     world::World,
 };
@@ -39,13 +40,24 @@ impl State {
             })
             .await
             .unwrap();
-        println!("Using {:?}", adapter.get_info().backend);
+        let backend = adapter.get_info().backend;
+        println!("Using {:?}", backend);
+
+        // The draws of chunks carry the offset of their chunk in their first instance, which
+        // Vulkan ignores in indirect draws unless `drawIndirectFirstInstance` is enabled, and
+        // wgpu never enables it. Chunks are drawn with a call per draw without
+        // multi-draw-indirect.
+        let multi_draw = if backend == wgpu::Backend::Vulkan {
+            wgpu::Features::empty()
+        } else {
+            adapter.features() & wgpu::Features::MULTI_DRAW_INDIRECT
+        };
 
         let (render_device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: Some("render_device"),
-                    features: wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY,
+                    features: wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY | multi_draw,
                     limits: wgpu::Limits::default(),
                 },
                 None,
@@ -93,6 +105,7 @@ impl State {
             swap_chain_descriptor,
             swap_chain,
             texture_manager: None,
+            settings: Settings::default(),
         };
 
         let mut texture_manager = TextureManager::new(&render_context);
@@ -136,11 +149,33 @@ impl State {
             .set_hotbar_cursor(&self.render_context, i);
     }
 
+    /// Switches the block in the selected slot of the hotbar to another one.
+    fn cycle_hotbar_block(&mut self, delta: isize) {
+        let slot = self.hud.widgets_hud.hotbar_cursor_position;
+        self.hud.hotbar_hud.cycle_block(slot, delta);
+    }
+
     fn input_keyboard(&mut self, key_code: VirtualKeyCode, state: ElementState) {
         let pressed = state == ElementState::Pressed;
 
         match key_code {
             VirtualKeyCode::F2 if pressed => self.player.creative ^= true,
+            VirtualKeyCode::F4 if pressed => {
+                self.render_context.settings.fancy_leaves ^= true;
+                self.world.update_all_chunk_geometry(&self.render_context);
+            }
+            VirtualKeyCode::F5 if pressed => {
+                let settings = &mut self.render_context.settings;
+                settings.anisotropy = if settings.anisotropy >= 16 {
+                    1
+                } else {
+                    settings.anisotropy * 2
+                };
+
+                let mut texture_manager = self.render_context.texture_manager.take().unwrap();
+                texture_manager.update_sampler(&self.render_context);
+                self.render_context.texture_manager = Some(texture_manager);
+            }
 
             // Hotbar
             VirtualKeyCode::Key1 if pressed => self.set_hotbar_cursor(0),
@@ -152,6 +187,8 @@ impl State {
             VirtualKeyCode::Key7 if pressed => self.set_hotbar_cursor(6),
             VirtualKeyCode::Key8 if pressed => self.set_hotbar_cursor(7),
             VirtualKeyCode::Key9 if pressed => self.set_hotbar_cursor(8),
+            VirtualKeyCode::Q if pressed => self.cycle_hotbar_block(-1),
+            VirtualKeyCode::E if pressed => self.cycle_hotbar_block(1),
 
             // Movement
             VirtualKeyCode::W => self.player.forward_pressed = pressed,
@@ -166,6 +203,7 @@ impl State {
 
                     // Not creative
                     (true, false) if self.player.grounded => 0.6,
+                    (true, false) if self.player.swimming => 0.3,
                     _ => self.player.up_speed,
                 };
             }
@@ -196,19 +234,26 @@ impl State {
                 ..
             } if self.mouse_grabbed => {
                 if button == &MouseButton::Left {
-                    self.world
-                        .break_at_crosshair(&self.render_context, &self.player.view.camera);
+                    // Blocks are broken instantly in creative mode, and over time otherwise
+                    if self.player.creative {
+                        self.world.break_at_crosshair(&self.player.view.camera);
+                    } else {
+                        self.player.break_pressed = true;
+                    }
                 } else if button == &MouseButton::Right {
                     if let Some(selected) = self.hud.selected_block() {
-                        self.world.place_at_crosshair(
-                            &self.render_context,
-                            &self.player.view.camera,
-                            selected,
-                        );
+                        self.world
+                            .place_at_crosshair(&self.player.view.camera, selected);
                     }
                 }
             }
 
+            WindowEvent::MouseInput {
+                button: MouseButton::Left,
+                state: ElementState::Released,
+                ..
+            } => self.player.break_pressed = false,
+
             WindowEvent::MouseWheel {
                 delta: MouseScrollDelta::LineDelta(_, delta),
                 ..
@@ -233,9 +278,16 @@ impl State {
         let view = &mut self.player.view;
         view.update_view_projection(&self.render_context);
 
+        // Broken blocks are re-meshed in the same frame by `World::update`
+        self.world.update_breaking(
+            &self.render_context,
+            dt,
+            self.player.break_pressed && !self.player.creative,
+        );
         self.world
             .update(&self.render_context, dt, render_time, &view.camera);
-        self.hud.update(&self.render_context, &view.camera);
+        self.hud
+            .update(&self.render_context, &view.camera, &self.world);
     }
 
     pub fn render(&mut self) -> anyhow::Result<(usize, Duration)> {
@@ -1,11 +1,14 @@
-use std::{num::NonZeroU32, ops::Range};
+use std::{
+    num::{NonZeroU32, NonZeroU8},
+    ops::Range,
+};
 
 use anyhow::Context;
 use cgmath::{Vector2, Zero};
-use image::{EncodableLayout, ImageBuffer, Rgba};
+use image::{EncodableLayout, ImageBuffer, Rgba, RgbaImage};
 use wgpu::Origin3d;
 
-use crate::render_context::RenderContext;
+use crate::{render_context::RenderContext, world::block_breaking::CRACK_STAGES};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -131,44 +134,21 @@ impl Texture {
             label,
         )
     }
-
-    pub fn from_bytes_atlas(
-        render_context: &RenderContext,
-        bytes: &[u8],
-        tile_size: Vector2<u32>,
-        label: &str,
-    ) -> anyhow::Result<Vec<Self>> {
-        let image = image::load_from_memory(bytes)?;
-        let rgba = image.into_rgba8();
-
-        let (width, height) = rgba.dimensions();
-        assert_eq!(width % tile_size.x, 0);
-        assert_eq!(height % tile_size.y, 0);
-
-        let mut tiles = Vec::new();
-        for y in (0..height).step_by(tile_size.y as usize) {
-            for x in (0..width).step_by(tile_size.x as usize) {
-                tiles.push(Self::from_rgba8(
-                    render_context,
-                    &rgba,
-                    Vector2::new(x, y),
-                    tile_size,
-                    &format!("{}({},{})", label, x, y),
-                )?);
-            }
-        }
-
-        Ok(tiles)
-    }
 }
 
-pub const TEXTURE_COUNT: usize = 44;
+pub const TEXTURE_COUNT: usize = 58;
+/// The width and height of every block texture, in texels.
+pub const TEXTURE_SIZE: u32 = 16;
+/// The number of mip levels of the block textures, down to a single texel.
+const MIP_LEVEL_COUNT: u32 = 32 - TEXTURE_SIZE.leading_zeros();
 
 pub struct TextureManager {
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub sampler: wgpu::Sampler,
 
-    pub textures: Vec<Texture>,
+    /// The block textures, in the order of their layers in the texture array.
+    pub images: Vec<RgbaImage>,
+    pub view: Option<wgpu::TextureView>,
     pub bind_group: Option<wgpu::BindGroup>,
 }
 
@@ -202,128 +182,166 @@ impl TextureManager {
                     ],
                 });
 
-        let sampler = render_context
+        Self {
+            bind_group_layout,
+            sampler: Self::create_sampler(render_context),
+
+            images: Vec::new(),
+            view: None,
+            bind_group: None,
+        }
+    }
+
+    /// Creates the sampler of the block textures, with the anisotropic filtering from the
+    /// settings.
+    ///
+    /// Textures repeat, so that greedy meshed quads can tile them with texture coordinates above
+    /// one. Without anisotropic filtering, texels stay sharp and only blend between the mip levels.
+    /// Anisotropic filtering is linear on some backends either way, so it's always linear with it.
+    fn create_sampler(render_context: &RenderContext) -> wgpu::Sampler {
+        let anisotropy = render_context.settings.anisotropy;
+        let filter = if anisotropy > 1 {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+
+        render_context
             .device
             .create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("block_texture_sampler"),
                 address_mode_u: wgpu::AddressMode::Repeat,
                 address_mode_v: wgpu::AddressMode::Repeat,
                 address_mode_w: wgpu::AddressMode::Repeat,
-                mag_filter: wgpu::FilterMode::Nearest,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                anisotropy_clamp: NonZeroU8::new(anisotropy),
                 ..wgpu::SamplerDescriptor::default()
-            });
+            })
+    }
 
-        Self {
-            bind_group_layout,
-            sampler,
+    /// Recreates the sampler and bind group after the anisotropy in the settings changed.
+    pub fn update_sampler(&mut self, render_context: &RenderContext) {
+        self.sampler = Self::create_sampler(render_context);
+        self.create_bind_group(render_context);
+    }
 
-            textures: Vec::new(),
-            bind_group: None,
-        }
+    fn create_bind_group(&mut self, render_context: &RenderContext) {
+        self.bind_group = Some(render_context.device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("Block texture bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(self.view.as_ref().unwrap()),
+                    },
+                ],
+            },
+        ));
     }
 
     pub fn load_all(&mut self, render_context: &RenderContext) -> anyhow::Result<()> {
-        let tile_size = Vector2::new(16, 16);
-
-        self.load(render_context, "assets/block/cobblestone.png")?; // 0
-        self.load(render_context, "assets/block/dirt.png")?; // 1
-        self.load(render_context, "assets/block/stone.png")?; // 2
-        self.load(render_context, "assets/grass_block_top_plains.png")?; // 3
-        self.load(render_context, "assets/grass_block_side_plains.png")?; // 4
-        self.load(render_context, "assets/block/bedrock.png")?; // 5
-        self.load(render_context, "assets/block/sand.png")?; // 6
-        self.load(render_context, "assets/block/gravel.png")?; // 7
-        self.load_atlas(render_context, "assets/block/water_still.png", tile_size)?; // 8 - 39
-        self.load(render_context, "assets/block/oak_log.png")?; // 40
-        self.load(render_context, "assets/block/oak_log_top.png")?; // 41
-        self.load(render_context, "assets/block/oak_planks.png")?; // 42
-        self.load(render_context, "assets/block/oak_leaves.png")?; // 43
-        assert_eq!(TEXTURE_COUNT, self.textures.len());
+        let tile_size = Vector2::new(TEXTURE_SIZE, TEXTURE_SIZE);
+
+        self.load("assets/block/cobblestone.png")?; // 0
+        self.load("assets/block/dirt.png")?; // 1
+        self.load("assets/block/stone.png")?; // 2
+        self.load("assets/grass_block_top_plains.png")?; // 3
+        self.load("assets/grass_block_side_plains.png")?; // 4
+        self.load("assets/block/bedrock.png")?; // 5
+        self.load("assets/block/sand.png")?; // 6
+        self.load("assets/block/gravel.png")?; // 7
+        self.load_atlas("assets/block/water_still.png", tile_size)?; // 8 - 39
+        self.load("assets/block/oak_log.png")?; // 40
+        self.load("assets/block/oak_log_top.png")?; // 41
+        self.load("assets/block/oak_planks.png")?; // 42
+        self.load("assets/block/oak_leaves.png")?; // 43
+        self.load("assets/block/grass.png")?; // 44
+        self.load("assets/block/poppy.png")?; // 45
+        for stage in 0..CRACK_STAGES {
+            let path = format!("assets/block/destroy_stage_{}.png", stage);
+            self.load(&path)?; // 46 - 55
+        }
+        self.load("assets/block/torch.png")?; // 56
+        self.load("assets/block/glowstone.png")?; // 57
+        assert_eq!(TEXTURE_COUNT, self.images.len());
 
         let texture_array = render_context
             .device
             .create_texture(&wgpu::TextureDescriptor {
-                label: None,
+                label: Some("block_texture_array"),
                 size: wgpu::Extent3d {
-                    width: 16,
-                    height: 16,
+                    width: TEXTURE_SIZE,
+                    height: TEXTURE_SIZE,
                     depth_or_array_layers: TEXTURE_COUNT as u32,
                 },
-                mip_level_count: 1,
+                mip_level_count: MIP_LEVEL_COUNT,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Rgba8UnormSrgb,
                 usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
             });
 
-        let mut encoder =
-            render_context
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("texture copy encoder"),
-                });
-
-        for (i, texture) in self.textures.iter().enumerate() {
-            encoder.copy_texture_to_texture(
-                wgpu::ImageCopyTexture {
-                    texture: &texture.texture,
-                    mip_level: 0,
-                    origin: Origin3d::ZERO,
-                },
-                wgpu::ImageCopyTexture {
-                    texture: &texture_array,
-                    mip_level: 0,
-                    origin: Origin3d {
-                        x: 0,
-                        y: 0,
-                        z: i as u32,
+        for (layer, image) in self.images.iter().enumerate() {
+            for (mip_level, mip) in mip_chain(image).iter().enumerate() {
+                let (width, height) = mip.dimensions();
+                render_context.queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &texture_array,
+                        mip_level: mip_level as u32,
+                        origin: Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
                     },
-                },
-                wgpu::Extent3d {
-                    width: 16,
-                    height: 16,
-                    depth_or_array_layers: 1,
-                },
-            )
+                    mip.as_bytes(),
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(4 * width),
+                        rows_per_image: NonZeroU32::new(height),
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
         }
 
-        render_context
-            .queue
-            .submit(std::iter::once(encoder.finish()));
-
-        let view = texture_array.create_view(&wgpu::TextureViewDescriptor {
+        self.view = Some(texture_array.create_view(&wgpu::TextureViewDescriptor {
             label: None,
             dimension: Some(wgpu::TextureViewDimension::D2Array),
             array_layer_count: NonZeroU32::new(TEXTURE_COUNT as u32),
             ..wgpu::TextureViewDescriptor::default()
-        });
-
-        self.bind_group = Some(render_context.device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                label: Some(&("Block texture bind group")),
-                layout: &self.bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&view),
-                    },
-                ],
-            },
-        ));
+        }));
+        self.create_bind_group(render_context);
 
         Ok(())
     }
 
-    pub fn load(&mut self, render_context: &RenderContext, path: &str) -> anyhow::Result<usize> {
+    pub fn load(&mut self, path: &str) -> anyhow::Result<usize> {
         let bytes = std::fs::read(path).context(format!("Failed to load {}", path))?;
-        let texture = Texture::from_bytes(render_context, &bytes, path)
-            .context(format!("Failed to decode {}", path))?;
+        let image = image::load_from_memory(&bytes)
+            .context(format!("Failed to decode {}", path))?
+            .into_rgba8();
+        anyhow::ensure!(
+            image.dimensions() == (TEXTURE_SIZE, TEXTURE_SIZE),
+            "{} is not {}x{}",
+            path,
+            TEXTURE_SIZE,
+            TEXTURE_SIZE
+        );
 
-        let id = self.textures.len();
-        self.textures.push(texture);
+        let id = self.images.len();
+        self.images.push(image);
 
         println!("loaded {} to {}", path, id);
         Ok(id)
@@ -331,19 +349,154 @@ impl TextureManager {
 
     pub fn load_atlas(
         &mut self,
-        render_context: &RenderContext,
         path: &str,
         tile_size: Vector2<u32>,
     ) -> anyhow::Result<Range<usize>> {
         let bytes = std::fs::read(path).context(format!("Failed to load {}", path))?;
-        let mut textures = Texture::from_bytes_atlas(render_context, &bytes, tile_size, path)
-            .context(format!("Failed to decode {}", path))?;
+        let image = image::load_from_memory(&bytes)
+            .context(format!("Failed to decode {}", path))?
+            .into_rgba8();
 
-        let start = self.textures.len();
-        self.textures.append(&mut textures);
-        let end = self.textures.len();
+        let (width, height) = image.dimensions();
+        assert_eq!(width % tile_size.x, 0);
+        assert_eq!(height % tile_size.y, 0);
+
+        let start = self.images.len();
+        for y in (0..height).step_by(tile_size.y as usize) {
+            for x in (0..width).step_by(tile_size.x as usize) {
+                let tile = image::imageops::crop_imm(&image, x, y, tile_size.x, tile_size.y);
+                self.images.push(tile.to_image());
+            }
+        }
+        let end = self.images.len();
 
         println!("loaded atlas {} to {}..{}", path, start, end);
         Ok(start..end)
     }
 }
+
+/// Returns the image and every smaller mip level of it, each half the size of the last, down to
+/// a single texel.
+///
+/// Every texel is the average of the 2x2 texels it covers in the level above. As the sizes are
+/// powers of two, these never straddle the edge of the image, so the levels keep tiling
+/// seamlessly when the texture repeats. The colors are averaged in linear space and weighted by
+/// their alpha, so transparent texels don't darken the edges of e.g. leaves.
+///
+/// Averaging thins out the alpha of cutout textures like plants, whose texels are discarded below
+/// `CUTOUT_ALPHA`, so the alpha of a level is scaled up until at least the same share of its
+/// texels is drawn as in the image. Otherwise they'd disappear in the distance.
+fn mip_chain(image: &RgbaImage) -> Vec<RgbaImage> {
+    let to_linear = |c: u8| (c as f32 / 255.0).powf(2.2);
+    let to_srgb = |c: f32| (c.powf(1.0 / 2.2) * 255.0).round() as u8;
+
+    let image_coverage = coverage(image, 1.0);
+    let mut levels = vec![image.clone()];
+    loop {
+        let last = levels.last().unwrap();
+        let (width, height) = last.dimensions();
+        if width == 1 && height == 1 {
+            return levels;
+        }
+
+        let mut level = RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+            let mut color = [0.0; 3];
+            let mut alpha = 0.0;
+            for (dx, dy) in itertools::iproduct!(0..2, 0..2) {
+                let texel =
+                    last.get_pixel((2 * x + dx).min(width - 1), (2 * y + dy).min(height - 1));
+                let a = texel[3] as f32 / 255.0;
+                for (sum, c) in color.iter_mut().zip(texel.0.iter()) {
+                    *sum += to_linear(*c) * a;
+                }
+                alpha += a;
+            }
+
+            if alpha == 0.0 {
+                return Rgba([0, 0, 0, 0]);
+            }
+            Rgba([
+                to_srgb(color[0] / alpha),
+                to_srgb(color[1] / alpha),
+                to_srgb(color[2] / alpha),
+                (alpha / 4.0 * 255.0).round() as u8,
+            ])
+        });
+
+        if coverage(&level, 1.0) < image_coverage {
+            // Coverage only grows with the scale, and any texel that isn't fully transparent is
+            // drawn at the largest one
+            let (mut low, mut high) = (1.0, 255.0);
+            for _ in 0..16 {
+                let middle = (low + high) / 2.0;
+                if coverage(&level, middle) < image_coverage {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            for texel in level.pixels_mut() {
+                texel[3] = (texel[3] as f32 * high).min(255.0).round() as u8;
+            }
+        }
+        levels.push(level);
+    }
+}
+
+/// The alpha below which the cutout pipelines discard texels, i.e. `main_cutout` in `world.wgsl`.
+const CUTOUT_ALPHA: f32 = 0.5 * 255.0;
+
+/// Returns the share of the texels of the image that aren't discarded by the cutout pipelines,
+/// after their alpha is multiplied by `scale`.
+fn coverage(image: &RgbaImage, scale: f32) -> f32 {
+    let covered = image
+        .pixels()
+        .filter(|texel| texel[3] as f32 * scale >= CUTOUT_ALPHA)
+        .count();
+    covered as f32 / image.pixels().len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_goes_down_to_one_texel() {
+        let image = RgbaImage::from_pixel(TEXTURE_SIZE, TEXTURE_SIZE, Rgba([10, 20, 30, 255]));
+        let levels = mip_chain(&image);
+        assert_eq!(levels.len(), MIP_LEVEL_COUNT as usize);
+        assert_eq!(levels.last().unwrap().dimensions(), (1, 1));
+        for level in levels {
+            assert!(level
+                .pixels()
+                .all(|texel| *texel == Rgba([10, 20, 30, 255])));
+        }
+    }
+
+    #[test]
+    fn transparent_texels_dont_darken() {
+        let image = RgbaImage::from_fn(2, 2, |x, _| {
+            if x == 0 {
+                Rgba([200, 100, 50, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let levels = mip_chain(&image);
+        assert_eq!(levels[1].get_pixel(0, 0), &Rgba([200, 100, 50, 128]));
+    }
+
+    #[test]
+    fn sparse_cutouts_dont_disappear() {
+        let image = RgbaImage::from_fn(4, 4, |x, y| {
+            if (x, y) == (0, 0) {
+                Rgba([200, 100, 50, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        for level in mip_chain(&image) {
+            assert!(coverage(&level, 1.0) >= 1.0 / 16.0);
+        }
+    }
+}
@@ -2,6 +2,8 @@ use std::mem::size_of;
 
 use wgpu::VertexAttribute;
 
+use crate::world::block::TINTS;
+
 pub trait Vertex {
     fn descriptor() -> wgpu::VertexBufferLayout<'static>;
 }
@@ -40,17 +42,19 @@ impl Vertex for HudVertex {
 /// Represents a vertex in world geometry.
 ///
 /// Aside from the usual vertex position, texture coordinates and normal, this "vertex" also
-/// contains whether the block is highlighted (i.e. the player is pointing at the block), its
-/// texture index (to address the texture arrays) and a color multiplier.
+/// contains its texture index (to address the texture arrays), a color multiplier, the sky and
+/// block light level (from 0 to 1) it is lit with and how much it's darkened by ambient
+/// occlusion.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct BlockVertex {
     pub position: [f32; 3],
     pub texture_coordinates: [f32; 2],
     pub normal: [f32; 3],
-    pub highlighted: i32,
     pub texture_id: i32,
     pub color: [f32; 4],
+    pub light: [f32; 2],
+    pub ambient_occlusion: f32,
 }
 
 const BLOCK_VERTEX_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
@@ -58,8 +62,9 @@ const BLOCK_VERTEX_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
     1 => Float32x2,
     2 => Float32x3,
     3 => Sint32,
-    4 => Sint32,
-    5 => Float32x4,
+    4 => Float32x4,
+    5 => Float32x2,
+    6 => Float32,
 ];
 
 impl Vertex for BlockVertex {
@@ -71,3 +76,197 @@ impl Vertex for BlockVertex {
         }
     }
 }
+
+/// Represents a vertex of the selection outline, which only has a position.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OutlineVertex {
+    pub position: [f32; 3],
+}
+
+const OUTLINE_VERTEX_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
+    0 => Float32x3,
+];
+
+impl Vertex for OutlineVertex {
+    fn descriptor() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: OUTLINE_VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+/// Represents a vertex of the far terrain, which has its shading baked into its color.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FarTerrainVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+const FAR_TERRAIN_VERTEX_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
+    0 => Float32x3,
+    1 => Float32x3,
+];
+
+impl Vertex for FarTerrainVertex {
+    fn descriptor() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: FAR_TERRAIN_VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+/// The number of steps a block is divided into by the position of a `ChunkVertex`.
+const CHUNK_VERTEX_STEPS: f32 = 16.0;
+/// The largest position or texture coordinate a `ChunkVertex` can hold in its 10 bits.
+const CHUNK_VERTEX_MAX: f32 = 1023.0 / CHUNK_VERTEX_STEPS;
+
+// The tint index is stored in 2 bits
+const _: () = assert!(TINTS.len() <= 4);
+
+/// Represents a vertex in chunk geometry, packed into 12 bytes.
+///
+/// Chunks make up almost all of the geometry in the world, so their vertices only store what
+/// `BlockVertex` stores in a fraction of the space, which `main_chunk` in `world.wgsl` decodes:
+///
+/// * `position` - The position relative to the chunk in 1/16 blocks, 10 bits per axis (X, Y, Z
+///   from the lowest bits). The offset of the chunk is provided per draw.
+/// * `texture_coordinates` - The texture coordinates in 1/16 textures (10 bits each), the face
+///   index in the same order as `NEIGHBOURS` (3 bits) and the index of the color in `TINTS`
+///   (2 bits).
+/// * `material` - The texture index (8 bits), the sky and block light levels (4 bits each) and
+///   the ambient occlusion brightness (8 bits).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChunkVertex {
+    pub position: u32,
+    pub texture_coordinates: u32,
+    pub material: u32,
+}
+
+const CHUNK_VERTEX_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
+    0 => Uint32,
+    1 => Uint32,
+    2 => Uint32,
+];
+
+impl ChunkVertex {
+    /// Packs a vertex of a chunk with its origin at `chunk_origin`.
+    pub fn pack(vertex: &BlockVertex, chunk_origin: [f32; 3]) -> Self {
+        let fixed = |value: f32| {
+            let fixed = (value * CHUNK_VERTEX_STEPS).round();
+            // Out of range values would spill into the neighbouring fields
+            assert!(
+                (0.0..=1023.0).contains(&fixed),
+                "{} doesn't fit into a ChunkVertex",
+                value
+            );
+            fixed as u32
+        };
+        let unorm = |value: f32, max: f32| (value * max).round() as u32;
+
+        let [x, y, z] = vertex.position;
+        let position = fixed(x - chunk_origin[0])
+            | fixed(y - chunk_origin[1]) << 10
+            | fixed(z - chunk_origin[2]) << 20;
+
+        let [nx, ny, nz] = vertex.normal;
+        let face = if nx < 0.0 {
+            0
+        } else if nx > 0.0 {
+            1
+        } else if nz < 0.0 {
+            2
+        } else if nz > 0.0 {
+            3
+        } else if ny < 0.0 {
+            4
+        } else {
+            5
+        };
+        let tint = TINTS
+            .iter()
+            .position(|&tint| tint == vertex.color)
+            .unwrap_or_else(|| panic!("{:?} isn't one of the TINTS", vertex.color))
+            as u32;
+
+        // Greedy quads repeat their texture once per block, so their texture coordinates grow
+        // with their size
+        let [u, v] = vertex.texture_coordinates;
+        assert!(
+            u <= CHUNK_VERTEX_MAX && v <= CHUNK_VERTEX_MAX,
+            "texture coordinates {:?} of a quad longer than {} blocks don't fit into a ChunkVertex",
+            vertex.texture_coordinates,
+            CHUNK_VERTEX_MAX,
+        );
+        let texture_coordinates = fixed(u) | fixed(v) << 10 | face << 20 | tint << 23;
+
+        let [sky, block] = vertex.light;
+        let material = vertex.texture_id as u32
+            | unorm(sky, 15.0) << 8
+            | unorm(block, 15.0) << 12
+            | unorm(vertex.ambient_occlusion, 255.0) << 16;
+
+        Self {
+            position,
+            texture_coordinates,
+            material,
+        }
+    }
+
+    /// Returns the WGSL source of `tint`, which `main_chunk` in `world.wgsl` uses to look up the
+    /// color of a vertex in `TINTS`.
+    pub fn tint_wgsl() -> String {
+        let vec4 =
+            |[r, g, b, a]: [f32; 4]| format!("vec4<f32>({:?}, {:?}, {:?}, {:?})", r, g, b, a);
+
+        let mut source = String::from("fn tint(index: u32) -> vec4<f32> {\n");
+        for (index, &tint) in TINTS.iter().enumerate().skip(1) {
+            source += &format!(
+                "    if (index == {}u) {{ return {}; }}\n",
+                index,
+                vec4(tint)
+            );
+        }
+        source += &format!("    return {};\n}}\n", vec4(TINTS[0]));
+        source
+    }
+}
+
+impl Vertex for ChunkVertex {
+    fn descriptor() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: CHUNK_VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+/// The per-draw data of chunk geometry, read by `main_chunk` in `world.wgsl` as an instance
+/// attribute, so the draws of many chunks can be made from the same buffers.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChunkInstance {
+    /// The position of the origin of the chunk, which its `ChunkVertex`es are relative to.
+    pub offset: [f32; 3],
+}
+
+const CHUNK_INSTANCE_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
+    3 => Float32x3,
+];
+
+impl Vertex for ChunkInstance {
+    fn descriptor() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: CHUNK_INSTANCE_ATTRIBUTES,
+        }
+    }
+}
@@ -5,14 +5,21 @@ use wgpu::{BindGroup, BindGroupLayout, Buffer, BufferDescriptor, BufferUsage};
 
 use crate::{
     aabb::Aabb,
-    camera::{Camera, Projection, OPENGL_TO_WGPU_MATRIX},
+    camera::{Camera, Projection},
+    frustum::Frustum,
     render_context::RenderContext,
+    world::{chunk::CHUNK_ISIZE, far_terrain::FAR_TERRAIN_DISTANCE},
 };
 
+/// The distance of the far plane, which has to reach the corners of the far terrain. It's this far
+/// from the near plane without z-fighting because the depth is reversed.
+const Z_FAR: f32 = (FAR_TERRAIN_DISTANCE * CHUNK_ISIZE) as f32 * 1.5;
+
 pub struct View {
     position_vector: Vector4<f32>,
     projection_matrix: Matrix4<f32>,
     pub frustrum_aabb: Aabb,
+    pub frustum: Frustum,
 
     pub camera: Camera,
     pub projection: Projection,
@@ -30,6 +37,12 @@ impl View {
         }
     }
 
+    /// Returns the matrix projecting the world onto the screen, which changes whenever the camera
+    /// moves or turns.
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix
+    }
+
     pub fn new(render_context: &RenderContext) -> Self {
         let camera = Camera::new(
             (10.0, 140.0, 10.0).into(),
@@ -42,7 +55,7 @@ impl View {
             render_context.swap_chain_descriptor.height,
             cgmath::Deg(45.0),
             0.1,
-            300.0,
+            Z_FAR,
         );
 
         let buffer = render_context.device.create_buffer(&BufferDescriptor {
@@ -84,6 +97,7 @@ impl View {
             position_vector: Vector4::zero(),
             projection_matrix: Matrix4::identity(),
             frustrum_aabb: Aabb::default(),
+            frustum: Frustum::from_matrix(Matrix4::identity()),
             camera,
             projection,
 
@@ -98,29 +112,38 @@ impl View {
         self.projection_matrix =
             self.projection.calculate_matrix() * self.camera.calculate_matrix();
         self.frustrum_aabb = self.frustrum_aabb();
+        self.frustum = Frustum::from_matrix(self.projection_matrix);
 
         render_context
             .queue
             .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.to_raw()]));
     }
 
+    /// Returns whether any part of the box is inside the view.
+    ///
+    /// The bounding box of the frustum is checked as well, as it catches some of the boxes near
+    /// its edges that the planes let through.
+    pub fn is_visible(&self, aabb: &Aabb) -> bool {
+        aabb.intersects(&self.frustrum_aabb) && self.frustum.intersects(aabb)
+    }
+
     fn frustrum_aabb(&self) -> Aabb {
-        let projection = OPENGL_TO_WGPU_MATRIX.invert().unwrap() * self.projection_matrix;
-        let inverse_matrix = projection.invert().unwrap();
+        let inverse_matrix = self.projection_matrix.invert().unwrap();
 
+        // The corners of wgpu's clip space, which has depths from 0 to 1
         let corners = [
             Vector4::new(-1.0, -1.0, 1.0, 1.0),
-            Vector4::new(-1.0, -1.0, -1.0, 1.0),
+            Vector4::new(-1.0, -1.0, 0.0, 1.0),
             Vector4::new(-1.0, 1.0, 1.0, 1.0),
-            Vector4::new(-1.0, 1.0, -1.0, 1.0),
+            Vector4::new(-1.0, 1.0, 0.0, 1.0),
             Vector4::new(1.0, -1.0, 1.0, 1.0),
-            Vector4::new(1.0, -1.0, -1.0, 1.0),
+            Vector4::new(1.0, -1.0, 0.0, 1.0),
             Vector4::new(1.0, 1.0, 1.0, 1.0),
-            Vector4::new(1.0, 1.0, -1.0, 1.0),
+            Vector4::new(1.0, 1.0, 0.0, 1.0),
         ];
 
         let mut min = Vector4::new(f32::INFINITY, f32::INFINITY, f32::INFINITY, 1.0);
-        let mut max = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let mut max = Vector4::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY, 1.0);
         for corner in corners {
             let corner = inverse_matrix * corner;
             let corner = corner / corner.w;
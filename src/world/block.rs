@@ -2,6 +2,29 @@ use cgmath::Vector4;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use crate::{settings::Settings, world::block_shape::BlockShape};
+
+/// Determines in which render pass a block's geometry is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderClass {
+    /// Fully opaque geometry.
+    Opaque,
+    /// Geometry with fully transparent holes, where fragments below an alpha threshold are
+    /// discarded (e.g. leaves and plants).
+    Cutout,
+    /// Blended, see-through geometry (e.g. water).
+    Translucent,
+}
+
+/// The colors blocks are tinted with, in the order of `BlockType::tint`. `ChunkVertex` stores
+/// the index, and `main_chunk` in `world.wgsl` looks it up in a `tint` function generated from
+/// this table.
+pub const TINTS: [[f32; 4]; 3] = [
+    [1.0, 1.0, 1.0, 1.0],
+    [0.247, 0.463, 0.894, 0.8],
+    [0.478, 0.729, 0.126, 1.0],
+];
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
@@ -17,41 +40,204 @@ pub enum BlockType {
     OakLog,
     OakPlanks,
     OakLeaves,
+    StoneSlab,
+    OakSlab,
+    CobblestoneStairs,
+    OakStairs,
+    TallGrass,
+    Poppy,
+    Torch,
+    Glowstone,
 }
 
 impl BlockType {
     #[rustfmt::skip]
     pub const fn texture_indices(self) -> (usize, usize, usize, usize, usize, usize) {
         match self {
-            BlockType::Cobblestone => ( 0,  0,  0,  0,  0,  0),
-            BlockType::Dirt        => ( 1,  1,  1,  1,  1,  1),
-            BlockType::Stone       => ( 2,  2,  2,  2,  2,  2),
-            BlockType::Grass       => ( 4,  4,  4,  4,  1,  3),
-            BlockType::Bedrock     => ( 5,  5,  5,  5,  5,  5),
-            BlockType::Sand        => ( 6,  6,  6,  6,  6,  6),
-            BlockType::Gravel      => ( 7,  7,  7,  7,  7,  7),
-            BlockType::Water       => ( 8,  8,  8,  8,  8,  8), // up to 39
-            BlockType::OakLog      => (40, 40, 40, 40, 41, 41),
-            BlockType::OakPlanks   => (42, 42, 42, 42, 42, 42),
-            BlockType::OakLeaves   => (43, 43, 43, 43, 43, 43),
+            BlockType::Cobblestone       => ( 0,  0,  0,  0,  0,  0),
+            BlockType::Dirt              => ( 1,  1,  1,  1,  1,  1),
+            BlockType::Stone             => ( 2,  2,  2,  2,  2,  2),
+            BlockType::Grass             => ( 4,  4,  4,  4,  1,  3),
+            BlockType::Bedrock           => ( 5,  5,  5,  5,  5,  5),
+            BlockType::Sand              => ( 6,  6,  6,  6,  6,  6),
+            BlockType::Gravel            => ( 7,  7,  7,  7,  7,  7),
+            BlockType::Water             => ( 8,  8,  8,  8,  8,  8), // up to 39
+            BlockType::OakLog            => (40, 40, 40, 40, 41, 41),
+            BlockType::OakPlanks         => (42, 42, 42, 42, 42, 42),
+            BlockType::OakLeaves         => (43, 43, 43, 43, 43, 43),
+            BlockType::StoneSlab         => ( 2,  2,  2,  2,  2,  2),
+            BlockType::OakSlab           => (42, 42, 42, 42, 42, 42),
+            BlockType::CobblestoneStairs => ( 0,  0,  0,  0,  0,  0),
+            BlockType::OakStairs         => (42, 42, 42, 42, 42, 42),
+            BlockType::TallGrass         => (44, 44, 44, 44, 44, 44),
+            BlockType::Poppy             => (45, 45, 45, 45, 45, 45),
+            BlockType::Torch             => (56, 56, 56, 56, 56, 56),
+            BlockType::Glowstone         => (57, 57, 57, 57, 57, 57),
         }
     }
 
+    /// The index of the color in `TINTS` the block is tinted with.
     #[rustfmt::skip]
-    pub const fn color(self) -> Vector4<f32> {
+    pub const fn tint(self) -> usize {
         match self {
-            Self::Water     => Vector4::new(0.247, 0.463, 0.894, 1.0),
-            Self::OakLeaves => Vector4::new(0.478, 0.729, 0.126, 1.0),
-            _               => Vector4::new(1.0, 1.0, 1.0, 1.0),
+            Self::Water     => 1,
+            Self::OakLeaves => 2,
+            Self::TallGrass => 2,
+            _               => 0,
         }
     }
 
+    pub const fn color(self) -> Vector4<f32> {
+        let [r, g, b, a] = TINTS[self.tint()];
+        Vector4::new(r, g, b, a)
+    }
+
     pub const fn is_transparent(self) -> bool {
         matches!(self, BlockType::Water)
     }
+
+    /// Whether the player collides with the block.
+    pub const fn is_solid(self) -> bool {
+        !matches!(
+            self,
+            BlockType::Water | BlockType::TallGrass | BlockType::Poppy | BlockType::Torch
+        )
+    }
+
+    /// Whether the block can be targeted with the crosshair, for breaking it or placing blocks
+    /// against it. Rays pass through blocks that aren't selectable.
+    pub const fn is_selectable(self) -> bool {
+        !matches!(self, BlockType::Water)
+    }
+
+    /// The time it takes to break the block in seconds, or `None` if it is unbreakable outside of
+    /// creative mode.
+    #[rustfmt::skip]
+    pub const fn hardness(self) -> Option<f32> {
+        match self {
+            BlockType::Cobblestone       => Some(3.0),
+            BlockType::Dirt              => Some(0.75),
+            BlockType::Stone             => Some(2.25),
+            BlockType::Grass             => Some(0.9),
+            BlockType::Bedrock           => None,
+            BlockType::Sand              => Some(0.75),
+            BlockType::Gravel            => Some(0.9),
+            BlockType::Water             => None,
+            BlockType::OakLog            => Some(3.0),
+            BlockType::OakPlanks         => Some(3.0),
+            BlockType::OakLeaves         => Some(0.3),
+            BlockType::StoneSlab         => Some(3.0),
+            BlockType::OakSlab           => Some(3.0),
+            BlockType::CobblestoneStairs => Some(3.0),
+            BlockType::OakStairs         => Some(3.0),
+            BlockType::TallGrass         => Some(0.0),
+            BlockType::Poppy             => Some(0.0),
+            BlockType::Torch             => Some(0.0),
+            BlockType::Glowstone         => Some(0.45),
+        }
+    }
+
+    /// Whether the block falls down when there's no solid block below it.
+    pub const fn has_gravity(self) -> bool {
+        matches!(self, BlockType::Sand | BlockType::Gravel)
+    }
+
+    /// Whether the block fully blocks light, e.g. killing grass below it.
+    pub const fn is_opaque(self) -> bool {
+        matches!(self.shape(), BlockShape::Cube)
+            && !matches!(self, BlockType::Water | BlockType::OakLeaves)
+    }
+
+    /// The level of the block light the block emits.
+    pub const fn light_emission(self) -> u8 {
+        match self {
+            BlockType::Glowstone => 15,
+            BlockType::Torch => 14,
+            _ => 0,
+        }
+    }
+
+    /// The number of ticks after which the block reacts to a neighbouring block changing, if it
+    /// reacts at all.
+    pub const fn update_delay(self) -> Option<u64> {
+        match self {
+            BlockType::Water => Some(5),
+            BlockType::Sand | BlockType::Gravel => Some(2),
+            _ => None,
+        }
+    }
+
+    /// Whether the block does something when it is randomly ticked, e.g. grass spreading.
+    pub const fn has_random_ticks(self) -> bool {
+        matches!(self, BlockType::Grass)
+    }
+
+    pub const fn render_class(self, settings: &Settings) -> RenderClass {
+        match self {
+            BlockType::Water => RenderClass::Translucent,
+            BlockType::OakLeaves if settings.fancy_leaves => RenderClass::Cutout,
+            BlockType::TallGrass | BlockType::Poppy | BlockType::Torch => RenderClass::Cutout,
+            _ => RenderClass::Opaque,
+        }
+    }
+
+    pub const fn shape(self) -> BlockShape {
+        match self {
+            BlockType::StoneSlab | BlockType::OakSlab => BlockShape::Slab,
+            BlockType::CobblestoneStairs | BlockType::OakStairs => BlockShape::Stairs,
+            BlockType::TallGrass | BlockType::Poppy => BlockShape::Cross,
+            BlockType::Torch => BlockShape::Torch,
+            _ => BlockShape::Cube,
+        }
+    }
+}
+
+/// The horizontal direction a block is facing, for shapes that are not symmetric (e.g. stairs).
+///
+/// For stairs, this is the side on which the tall part of the stairs sits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum Facing {
+    Left,
+    Right,
+    Back,
+    #[default]
+    Front,
+}
+
+impl Facing {
+    /// Returns the facing closest to the horizontal direction `(x, z)`.
+    pub fn from_direction(x: f32, z: f32) -> Self {
+        if x.abs() > z.abs() {
+            if x < 0.0 {
+                Facing::Left
+            } else {
+                Facing::Right
+            }
+        } else if z < 0.0 {
+            Facing::Back
+        } else {
+            Facing::Front
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Block {
     pub block_type: BlockType,
+    #[serde(default)]
+    pub facing: Facing,
+    /// The level of fluid blocks (see `fluid`), 0 for sources and other blocks.
+    #[serde(default)]
+    pub level: u8,
+}
+
+impl Block {
+    pub const fn new(block_type: BlockType) -> Self {
+        Self {
+            block_type,
+            facing: Facing::Front,
+            level: 0,
+        }
+    }
 }
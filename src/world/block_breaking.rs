@@ -0,0 +1,102 @@
+use cgmath::{Point3, Vector3, Vector4};
+use wgpu::BufferUsage;
+
+use crate::{
+    geometry::Geometry,
+    geometry_buffers::GeometryBuffers,
+    render_context::RenderContext,
+    vertex::BlockVertex,
+    world::{block::Block, face_flags::FACE_ALL, light::Light},
+};
+
+/// The number of crack stages drawn over a block while it is being broken.
+pub const CRACK_STAGES: usize = 10;
+
+/// Texture index of the first crack stage.
+const CRACK_TEXTURE: usize = 46; // up to 55
+
+/// Keeps track of the progress of breaking a block, and the crack overlay drawn over it.
+pub struct BlockBreaking {
+    pub position: Point3<isize>,
+    /// How far the block has been broken, from 0 to 1.
+    pub progress: f32,
+    stage: usize,
+    /// The light next to each face of the block, which the crack overlay is lit with.
+    light: [Light; 6],
+    pub buffers: GeometryBuffers<u16>,
+}
+
+impl BlockBreaking {
+    pub fn new(
+        render_context: &RenderContext,
+        block: &Block,
+        position: Point3<isize>,
+        light: [Light; 6],
+    ) -> Self {
+        Self {
+            position,
+            progress: 0.0,
+            stage: 0,
+            light,
+            buffers: Self::crack_buffers(render_context, block, position, 0, light),
+        }
+    }
+
+    /// Breaks the block further by `progress`, updating the crack overlay if it reached the next
+    /// stage.
+    ///
+    /// Returns whether the block is broken.
+    pub fn progress(
+        &mut self,
+        render_context: &RenderContext,
+        block: &Block,
+        progress: f32,
+    ) -> bool {
+        self.progress += progress;
+        if self.progress >= 1.0 {
+            return true;
+        }
+
+        let stage = ((self.progress * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1);
+        if stage != self.stage {
+            self.stage = stage;
+            self.buffers =
+                Self::crack_buffers(render_context, block, self.position, stage, self.light);
+        }
+
+        false
+    }
+
+    fn crack_buffers(
+        render_context: &RenderContext,
+        block: &Block,
+        position: Point3<isize>,
+        stage: usize,
+        light: [Light; 6],
+    ) -> GeometryBuffers<u16> {
+        let texture = CRACK_TEXTURE + stage;
+        let mut geometry: Geometry<BlockVertex, u16> = block.block_type.shape().to_geometry(
+            block.facing,
+            position,
+            FACE_ALL,
+            (texture, texture, texture, texture, texture, texture),
+            Vector4::new(1.0, 1.0, 1.0, 1.0),
+            light,
+            0,
+        );
+
+        // Grow the overlay slightly, so it doesn't z-fight with the block itself
+        let center = position.cast::<f32>().unwrap() + Vector3::new(0.5, 0.5, 0.5);
+        for vertex in &mut geometry.vertices {
+            for (coordinate, center) in vertex
+                .position
+                .iter_mut()
+                .zip(&[center.x, center.y, center.z])
+            {
+                *coordinate = center + (*coordinate - center) * 1.002;
+            }
+        }
+
+        GeometryBuffers::from_geometry(render_context, &geometry, BufferUsage::empty())
+    }
+}
@@ -0,0 +1,259 @@
+use cgmath::{Point3, Vector3, Vector4};
+
+use crate::{
+    aabb::Aabb,
+    geometry::Geometry,
+    vertex::BlockVertex,
+    world::{block::Facing, face_flags::*, light::Light},
+};
+
+/// The geometric shape of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockShape {
+    /// A full unit cube.
+    Cube,
+    /// The bottom half of a cube.
+    Slab,
+    /// A bottom slab with a quarter block on top, on the side the block is facing.
+    Stairs,
+    /// Two diagonal planes crossing in the middle of the block, used for plants.
+    Cross,
+    /// A thin stick standing in the middle of the block.
+    Torch,
+}
+
+impl BlockShape {
+    /// Returns the boxes making up the shape, in block-local coordinates (i.e. within 0..1).
+    ///
+    /// These are both used for rendering and for collision. `Cross` shapes have no boxes.
+    pub fn boxes(self, facing: Facing) -> Vec<Aabb> {
+        let aabb = |min: (f32, f32, f32), max: (f32, f32, f32)| Aabb {
+            min: min.into(),
+            max: max.into(),
+        };
+
+        match self {
+            BlockShape::Cube => vec![aabb((0.0, 0.0, 0.0), (1.0, 1.0, 1.0))],
+            BlockShape::Slab => vec![aabb((0.0, 0.0, 0.0), (1.0, 0.5, 1.0))],
+            BlockShape::Stairs => vec![
+                aabb((0.0, 0.0, 0.0), (1.0, 0.5, 1.0)),
+                match facing {
+                    Facing::Left => aabb((0.0, 0.5, 0.0), (0.5, 1.0, 1.0)),
+                    Facing::Right => aabb((0.5, 0.5, 0.0), (1.0, 1.0, 1.0)),
+                    Facing::Back => aabb((0.0, 0.5, 0.0), (1.0, 1.0, 0.5)),
+                    Facing::Front => aabb((0.0, 0.5, 0.5), (1.0, 1.0, 1.0)),
+                },
+            ],
+            BlockShape::Cross => vec![],
+            BlockShape::Torch => vec![aabb(
+                (7.0 / 16.0, 0.0, 7.0 / 16.0),
+                (9.0 / 16.0, 10.0 / 16.0, 9.0 / 16.0),
+            )],
+        }
+    }
+
+    /// Returns the bitmap of the faces that completely cover the side of the block they're on.
+    ///
+    /// Neighbouring faces touching one of these faces are hidden.
+    pub fn full_faces(self, facing: Facing) -> FaceFlags {
+        match self {
+            BlockShape::Cube => FACE_ALL,
+            BlockShape::Slab => FACE_BOTTOM,
+            BlockShape::Stairs => {
+                FACE_BOTTOM
+                    | match facing {
+                        Facing::Left => FACE_LEFT,
+                        Facing::Right => FACE_RIGHT,
+                        Facing::Back => FACE_BACK,
+                        Facing::Front => FACE_FRONT,
+                    }
+            }
+            BlockShape::Cross | BlockShape::Torch => FACE_NONE,
+        }
+    }
+
+    /// Converts a block with this shape to `Geometry` to be rendered.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The world position of the block.
+    /// * `visible_faces` - The faces on the sides of the block that aren't hidden by neighbours.
+    ///   Faces of the shape that don't lie on a side of the block are always visible.
+    /// * `texture_indices` and `color` - As returned by `BlockType`.
+    /// * `light` - The light next to each face of the block, in the same order as
+    ///   `texture_indices`.
+    /// * `start_index` - Which geometry index to start at.
+    #[allow(clippy::too_many_arguments)]
+    pub fn to_geometry(
+        self,
+        facing: Facing,
+        position: Point3<isize>,
+        visible_faces: FaceFlags,
+        texture_indices: (usize, usize, usize, usize, usize, usize),
+        color: Vector4<f32>,
+        light: [Light; 6],
+        start_index: u16,
+    ) -> Geometry<BlockVertex, u16> {
+        let position: Point3<f32> = position.cast().unwrap();
+        let color = color.into();
+
+        if self == BlockShape::Cross {
+            // Plants are inside the block, so they are lit by the brightest light around them
+            let light = light.iter().fold(Light::default(), |a, &b| a.max(b));
+            return Self::cross_geometry(
+                position,
+                texture_indices.0 as i32,
+                color,
+                light.to_vertex(),
+                start_index,
+            );
+        }
+
+        let t = texture_indices;
+        let faces = [
+            (FACE_LEFT, Vector3::new(-1, 0, 0), t.0, light[0]),
+            (FACE_RIGHT, Vector3::new(1, 0, 0), t.1, light[1]),
+            (FACE_BACK, Vector3::new(0, 0, -1), t.2, light[2]),
+            (FACE_FRONT, Vector3::new(0, 0, 1), t.3, light[3]),
+            (FACE_BOTTOM, Vector3::new(0, -1, 0), t.4, light[4]),
+            (FACE_TOP, Vector3::new(0, 1, 0), t.5, light[5]),
+        ];
+
+        let mut geometry = Geometry::default();
+        for aabb in self.boxes(facing) {
+            for &(face, normal, texture_id, light) in &faces {
+                let on_side = match face {
+                    FACE_LEFT => aabb.min.x == 0.0,
+                    FACE_RIGHT => aabb.max.x == 1.0,
+                    FACE_BACK => aabb.min.z == 0.0,
+                    FACE_FRONT => aabb.max.z == 1.0,
+                    FACE_BOTTOM => aabb.min.y == 0.0,
+                    _ => aabb.max.y == 1.0,
+                };
+                if on_side && visible_faces & face == FACE_NONE {
+                    continue;
+                }
+
+                let start_index = start_index + geometry.vertices.len() as u16;
+                geometry.append(&mut Self::box_face_geometry(
+                    &aabb,
+                    position,
+                    face,
+                    normal.cast().unwrap().into(),
+                    texture_id as i32,
+                    color,
+                    light.to_vertex(),
+                    start_index,
+                ));
+            }
+        }
+
+        geometry
+    }
+
+    /// Creates a single face of a box, with the same vertex order and texture mapping as `Quad`.
+    #[allow(clippy::too_many_arguments)]
+    #[rustfmt::skip]
+    fn box_face_geometry(
+        aabb: &Aabb,
+        position: Point3<f32>,
+        face: FaceFlags,
+        normal: [f32; 3],
+        texture_id: i32,
+        color: [f32; 4],
+        light: [f32; 2],
+        i: u16,
+    ) -> Geometry<BlockVertex, u16> {
+        let (a, b) = (aabb.min, aabb.max);
+        let (corners, indices) = match face {
+            FACE_LEFT => (
+                [(a.x, a.y, a.z), (a.x, a.y, b.z), (a.x, b.y, b.z), (a.x, b.y, a.z)],
+                [2 + i, i, 1 + i, 3 + i, i, 2 + i],
+            ),
+            FACE_RIGHT => (
+                [(b.x, a.y, a.z), (b.x, a.y, b.z), (b.x, b.y, b.z), (b.x, b.y, a.z)],
+                [1 + i, i, 2 + i, 2 + i, i, 3 + i],
+            ),
+            FACE_BACK => (
+                [(a.x, a.y, a.z), (a.x, b.y, a.z), (b.x, b.y, a.z), (b.x, a.y, a.z)],
+                [2 + i, i, 1 + i, 3 + i, i, 2 + i],
+            ),
+            FACE_FRONT => (
+                [(a.x, a.y, b.z), (a.x, b.y, b.z), (b.x, b.y, b.z), (b.x, a.y, b.z)],
+                [1 + i, i, 2 + i, 2 + i, i, 3 + i],
+            ),
+            FACE_BOTTOM => (
+                [(a.x, a.y, a.z), (a.x, a.y, b.z), (b.x, a.y, b.z), (b.x, a.y, a.z)],
+                [i, 2 + i, 1 + i, i, 3 + i, 2 + i],
+            ),
+            _ => (
+                [(a.x, b.y, a.z), (a.x, b.y, b.z), (b.x, b.y, b.z), (b.x, b.y, a.z)],
+                [i, 1 + i, 2 + i, i, 2 + i, 3 + i],
+            ),
+        };
+
+        let vertices = corners
+            .iter()
+            .map(|&(x, y, z)| {
+                // Map the block-local position to texture coordinates, so that partial faces show
+                // the matching part of the texture.
+                let texture_coordinates = match face {
+                    FACE_LEFT   => [1.0 - z, 1.0 - y],
+                    FACE_RIGHT  => [z,       1.0 - y],
+                    FACE_BACK   => [1.0 - x, 1.0 - y],
+                    FACE_FRONT  => [x,       1.0 - y],
+                    FACE_BOTTOM => [1.0 - x, z      ],
+                    _           => [x,       z      ],
+                };
+
+                BlockVertex {
+                    position: [position.x + x, position.y + y, position.z + z],
+                    texture_coordinates,
+                    normal,
+                    texture_id,
+                    color,
+                    light,
+                    ambient_occlusion: 1.0,
+                }
+            })
+            .collect();
+
+        Geometry::new(vertices, indices.to_vec())
+    }
+
+    /// Creates the two double-sided diagonal planes of a `Cross` shape.
+    #[rustfmt::skip]
+    fn cross_geometry(
+        position: Point3<f32>,
+        texture_id: i32,
+        color: [f32; 4],
+        light: [f32; 2],
+        start_index: u16,
+    ) -> Geometry<BlockVertex, u16> {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        // Plants are lit as if they were facing up, so both sides look the same
+        let normal = [0.0, 1.0, 0.0];
+        for &((x0, z0), (x1, z1)) in &[((0.0, 0.0), (1.0, 1.0)), ((0.0, 1.0), (1.0, 0.0))] {
+            let (x, y, z) = (position.x, position.y, position.z);
+            let i = start_index + vertices.len() as u16;
+            vertices.extend([
+                BlockVertex { position: [x + x0, y,       z + z0], texture_coordinates: [0.0, 1.0], texture_id, normal, color, light, ambient_occlusion: 1.0 },
+                BlockVertex { position: [x + x1, y,       z + z1], texture_coordinates: [1.0, 1.0], texture_id, normal, color, light, ambient_occlusion: 1.0 },
+                BlockVertex { position: [x + x1, y + 1.0, z + z1], texture_coordinates: [1.0, 0.0], texture_id, normal, color, light, ambient_occlusion: 1.0 },
+                BlockVertex { position: [x + x0, y + 1.0, z + z0], texture_coordinates: [0.0, 0.0], texture_id, normal, color, light, ambient_occlusion: 1.0 },
+            ]);
+            indices.extend([
+                // Front side
+                i, 1 + i, 2 + i,
+                i, 2 + i, 3 + i,
+                // Back side
+                i, 2 + i, 1 + i,
+                i, 3 + i, 2 + i,
+            ]);
+        }
+
+        Geometry::new(vertices, indices)
+    }
+}
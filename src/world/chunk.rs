@@ -1,44 +1,147 @@
-use std::collections::VecDeque;
-
 use crate::{
     aabb::Aabb,
     geometry::Geometry,
-    geometry_buffers::GeometryBuffers,
     render_context::RenderContext,
-    vertex::BlockVertex,
+    settings::Settings,
+    vertex::{BlockVertex, ChunkVertex, Vertex},
     view::View,
     world::{
-        block::{Block, BlockType},
+        block::{Block, BlockType, RenderClass},
+        block_shape::BlockShape,
+        chunk_arena::{ChunkArena, ChunkBuffers},
         face_flags::*,
-        quad::Quad,
+        fluid,
+        light::{BorderLight, Light, MAX_LIGHT, NEIGHBOURS},
+        quad::{Quad, FACE_CORNERS},
+        visibility::ChunkVisibility,
     },
 };
 use cgmath::{Point3, Vector3};
-use fxhash::{FxHashMap, FxHashSet};
-use noise::utils::{NoiseMapBuilder, PlaneMapBuilder};
+use fxhash::FxHasher;
+use noise::{
+    utils::{NoiseMapBuilder, PlaneMapBuilder},
+    NoiseFn,
+};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{
     de::{SeqAccess, Visitor},
     ser::SerializeSeq,
     Deserialize, Serialize, Serializer,
 };
-use wgpu::{BufferUsage, RenderPass};
+use std::hash::{Hash, Hasher};
 
 pub const CHUNK_SIZE: usize = 32;
 pub const CHUNK_ISIZE: isize = CHUNK_SIZE as isize;
 
+/// The height below which the air in generated terrain is filled with water.
+pub const SEA_LEVEL: isize = 128;
+
+const TERRAIN_NOISE_SCALE: f64 = 0.1 / 16.0 * CHUNK_SIZE as f64;
+const TERRAIN_NOISE_OFFSET: f64 = 0.0 / 16.0 * CHUNK_SIZE as f64;
+
+/// Returns the height of the grass block `Chunk::generate` puts on top of the terrain at the
+/// given block column, without generating any chunks.
+pub fn terrain_height(fbm: &noise::Fbm, x: isize, z: isize) -> isize {
+    let step = TERRAIN_NOISE_SCALE / CHUNK_SIZE as f64;
+    let point = [
+        x as f64 * step + TERRAIN_NOISE_OFFSET,
+        z as f64 * step + TERRAIN_NOISE_OFFSET,
+        0.0,
+    ];
+    let v = fbm.get(point) * 20.0 + SEA_LEVEL as f64;
+    v.round() as isize
+}
+
+/// Returns the plant `Chunk::generate` puts on the grass at the given block column, if any.
+fn terrain_plant(x: isize, z: isize) -> Option<BlockType> {
+    let mut hasher = FxHasher::default();
+    (x, z).hash(&mut hasher);
+    // The low bits of the hash repeat across neighbouring columns
+    match hasher.finish() >> 58 {
+        0 => Some(BlockType::Poppy),
+        1..=6 => Some(BlockType::TallGrass),
+        _ => None,
+    }
+}
+
+/// The blocks bordering a chunk on each side, in the same order as `NEIGHBOURS`.
+///
+/// The layers are indexed like `BorderLight`.
+pub type BorderBlocks = [[[Option<Block>; CHUNK_SIZE]; CHUNK_SIZE]; 6];
+
+/// Returns the borders of a chunk without any neighbours, i.e. one surrounded by air that is
+/// fully lit by the sky.
+pub fn isolated_borders() -> (Box<BorderBlocks>, Box<BorderLight>, BorderOccluders) {
+    (
+        Box::new([[[None; CHUNK_SIZE]; CHUNK_SIZE]; 6]),
+        Box::new([[[Light::new(MAX_LIGHT, 0); CHUNK_SIZE]; CHUNK_SIZE]; 6]),
+        BorderOccluders::from_fn(|_| false),
+    )
+}
+
+/// The size of a chunk with a layer of blocks around it.
+const PADDED_SIZE: usize = CHUNK_SIZE + 2;
+
+/// Which blocks around a chunk are opaque and darken the corners of the faces next to them,
+/// including the blocks diagonal to the edges and corners of the chunk that `BorderBlocks`
+/// leaves out.
+///
+/// Positions are relative to the chunk, from -1 to `CHUNK_SIZE` on every axis. The blocks inside
+/// the chunk itself aren't stored.
+#[derive(Clone)]
+pub struct BorderOccluders {
+    bits: Vec<u64>,
+}
+
+impl BorderOccluders {
+    /// Calls `is_opaque` with the position of every block around the chunk.
+    pub fn from_fn(mut is_opaque: impl FnMut(Vector3<isize>) -> bool) -> Self {
+        let mut bits = vec![0; PADDED_SIZE.pow(3).div_ceil(64)];
+        let range = -1..=CHUNK_ISIZE;
+        for (x, y, z) in itertools::iproduct!(range.clone(), range.clone(), range) {
+            let position = Vector3::new(x, y, z);
+            let inside = [x, y, z].iter().all(|c| (0..CHUNK_ISIZE).contains(c));
+            if !inside && is_opaque(position) {
+                let index = Self::index(position);
+                bits[index / 64] |= 1 << (index % 64);
+            }
+        }
+        Self { bits }
+    }
+
+    fn index(position: Vector3<isize>) -> usize {
+        let padded = position.map(|x| (x + 1) as usize);
+        (padded.y * PADDED_SIZE + padded.z) * PADDED_SIZE + padded.x
+    }
+
+    /// Returns whether the block at the given position around the chunk is opaque.
+    pub fn get(&self, position: Vector3<isize>) -> bool {
+        let index = Self::index(position);
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
 pub struct Chunk {
     pub blocks: [[[Option<Block>; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
-    pub buffers: Option<GeometryBuffers<u16>>,
-    pub full: bool,
+    /// The light level of every block, calculated by the light engine when the chunk is loaded.
+    pub light: [[[Light; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+    /// The latest mesh of the chunk on the GPU. `None` until the chunk is first meshed.
+    pub buffers: Option<ChunkBuffers>,
+    /// Which faces of the chunk can see each other, as of its latest mesh.
+    pub visibility: ChunkVisibility,
+    /// The version of the latest mesh queued for the chunk. Meshes with an older version were
+    /// made before the chunk last changed, and are dropped instead of uploaded.
+    pub mesh_version: u64,
 }
 
 impl Default for Chunk {
     fn default() -> Self {
         Self {
             blocks: [[[None; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            light: [[[Light::default(); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
             buffers: None,
-            full: false,
+            visibility: ChunkVisibility::default(),
+            mesh_version: 0,
         }
     }
 }
@@ -95,46 +198,163 @@ impl<'de> Deserialize<'de> for Chunk {
     }
 }
 
-impl Chunk {
-    pub fn render<'a>(
-        &'a self,
-        render_pass: &mut RenderPass<'a>,
-        position: &Point3<isize>,
-        view: &View,
-    ) -> usize {
-        if !self.is_visible(position * CHUNK_ISIZE, &view) {
-            // Frustrum culling
-            0
-        } else if let Some(buffers) = &self.buffers {
-            buffers.apply_buffers(render_pass);
-            buffers.draw_indexed(render_pass)
-        } else {
-            // Not loaded
-            println!("Trying to render non-loaded chunk {:?}", position);
-            0
+/// The most vertices a single sub-mesh can have, so all of them can be addressed by `u16`
+/// indices.
+pub const MAX_SUB_MESH_VERTICES: usize = u16::MAX as usize + 1;
+
+/// The geometry of a chunk, split by how it's rendered.
+///
+/// Each kind of geometry is split into sub-meshes of at most `MAX_SUB_MESH_VERTICES` vertices,
+/// since chunks with many small faces (e.g. a checkerboard) have more vertices than `u16`
+/// indices can address. Kinds the chunk has no geometry of have no sub-meshes.
+#[derive(Default)]
+pub struct ChunkMesh<V: Vertex = BlockVertex> {
+    pub opaque: Vec<Geometry<V, u16>>,
+    pub cutout: Vec<Geometry<V, u16>>,
+    pub translucent: Vec<Geometry<V, u16>>,
+}
+
+impl<V: Vertex + Copy + Default> ChunkMesh<V> {
+    /// Combines all sub-meshes into a single geometry, with the opaque geometry first and the
+    /// translucent geometry last.
+    pub fn to_geometry(&self) -> Geometry<V, u32> {
+        let mut combined = Geometry::default();
+        for geometry in self
+            .opaque
+            .iter()
+            .chain(&self.cutout)
+            .chain(&self.translucent)
+        {
+            let start_index = combined.vertices.len() as u32;
+            combined.vertices.extend_from_slice(&geometry.vertices);
+            combined.indices.extend(
+                geometry
+                    .indices
+                    .iter()
+                    .map(|&index| start_index + index as u32),
+            );
         }
+        combined
     }
+}
 
-    pub fn update_fullness(&mut self) {
-        for y in 0..CHUNK_SIZE {
-            for z in 0..CHUNK_SIZE {
-                for x in 0..CHUNK_SIZE {
-                    if self.blocks[y][z][x].is_none() {
-                        self.full = false;
-                        return;
-                    }
-                }
+impl ChunkMesh {
+    /// Converts the quads to geometry, split by how they're rendered.
+    pub fn from_quads(quads: Vec<Quad>, settings: &Settings) -> Self {
+        let mut opaque_quads = Vec::new();
+        let mut cutout_quads = Vec::new();
+        let mut translucent_quads = Vec::new();
+        for quad in quads {
+            let render_class = quad.block_type.map_or(RenderClass::Opaque, |block_type| {
+                block_type.render_class(settings)
+            });
+            match render_class {
+                RenderClass::Opaque => opaque_quads.push(quad),
+                RenderClass::Cutout => cutout_quads.push(quad),
+                RenderClass::Translucent => translucent_quads.push(quad),
             }
         }
 
-        self.full = true;
+        Self {
+            opaque: Self::quads_to_geometry(opaque_quads),
+            cutout: Self::quads_to_geometry(cutout_quads),
+            translucent: Self::quads_to_geometry(translucent_quads),
+        }
+    }
+
+    /// Converts the quads to sub-meshes of at most `MAX_SUB_MESH_VERTICES` vertices each.
+    fn quads_to_geometry(quads: Vec<Quad>) -> Vec<Geometry<BlockVertex, u16>> {
+        let mut sub_meshes = Vec::new();
+        let mut geometry: Geometry<BlockVertex, u16> = Default::default();
+        for quad in quads {
+            let mut quad_geometry = quad.to_geometry(0);
+            if geometry.vertices.len() + quad_geometry.vertices.len() > MAX_SUB_MESH_VERTICES {
+                sub_meshes.push(std::mem::take(&mut geometry));
+            }
+
+            let start_index = geometry.vertices.len() as u16;
+            for index in &mut quad_geometry.indices {
+                *index += start_index;
+            }
+            geometry.append(&mut quad_geometry);
+        }
+
+        if !geometry.indices.is_empty() {
+            sub_meshes.push(geometry);
+        }
+        sub_meshes
+    }
+
+    /// Packs the vertices of the mesh of the chunk at `chunk_coords` into `ChunkVertex`es, which
+    /// are relative to the chunk.
+    pub fn pack(self, chunk_coords: Point3<isize>) -> ChunkMesh<ChunkVertex> {
+        let origin = (chunk_coords * CHUNK_ISIZE).cast::<f32>().unwrap().into();
+        let pack = |sub_meshes: Vec<Geometry<BlockVertex, u16>>| {
+            sub_meshes
+                .into_iter()
+                .map(|geometry| {
+                    let vertices = geometry
+                        .vertices
+                        .iter()
+                        .map(|vertex| ChunkVertex::pack(vertex, origin))
+                        .collect();
+                    Geometry::new(vertices, geometry.indices)
+                })
+                .collect()
+        };
+
+        ChunkMesh {
+            opaque: pack(self.opaque),
+            cutout: pack(self.cutout),
+            translucent: pack(self.translucent),
+        }
     }
+}
+
+/// A block with at least one visible face, as found by `Chunk::cull_layer`.
+#[derive(Clone, Copy)]
+struct CulledBlock {
+    block: Block,
+    visible_faces: FaceFlags,
+    light: [Light; 6],
+    ao: [[u8; 4]; 6],
+}
+
+/// A visible face of a block in a slice of the chunk, that can be merged with the faces next to
+/// it by `Chunk::slice_to_quads`.
+#[derive(Clone, Copy)]
+struct MergeableFace {
+    block_type: BlockType,
+    light: Light,
+    ao: [u8; 4],
+}
 
+impl MergeableFace {
+    /// Returns whether the faces can be merged into a single quad, i.e. they look the same.
+    ///
+    /// Faces with different ambient occlusion on their corners aren't merged at all, since the
+    /// occlusion would be interpolated across the whole merged quad instead of a single face.
+    fn can_merge_with(&self, other: &MergeableFace, side: usize, settings: &Settings) -> bool {
+        let uniform_ao = |ao: [u8; 4]| ao.iter().all(|&corner| corner == ao[0]);
+        texture_index(self.block_type, side) == texture_index(other.block_type, side)
+            && self.block_type.color() == other.block_type.color()
+            && self.block_type.render_class(settings) == other.block_type.render_class(settings)
+            && self.light == other.light
+            && self.ao == other.ao
+            && uniform_ao(self.ao)
+    }
+}
+
+/// Returns the texture index of the given side of a block, in the same order as `NEIGHBOURS`.
+fn texture_index(block_type: BlockType, side: usize) -> usize {
+    let t = block_type.texture_indices();
+    [t.0, t.1, t.2, t.3, t.4, t.5][side]
+}
+
+impl Chunk {
     pub fn generate(&mut self, chunk_x: isize, chunk_y: isize, chunk_z: isize) {
         let fbm = noise::Fbm::new();
 
-        const TERRAIN_NOISE_SCALE: f64 = 0.1 / 16.0 * CHUNK_SIZE as f64;
-        const TERRAIN_NOISE_OFFSET: f64 = 0.0 / 16.0 * CHUNK_SIZE as f64;
         let terrain_noise = PlaneMapBuilder::new(&fbm)
             .set_size(CHUNK_SIZE, CHUNK_SIZE)
             .set_x_bounds(
@@ -163,7 +383,7 @@ impl Chunk {
 
         for z in 0..CHUNK_SIZE {
             for x in 0..CHUNK_SIZE {
-                let v = terrain_noise.get_value(x, z) * 20.0 + 128.0;
+                let v = terrain_noise.get_value(x, z) * 20.0 + SEA_LEVEL as f64;
                 let v = v.round() as isize;
 
                 let s = stone_noise.get_value(x, z) * 20.0 + 4.5;
@@ -171,35 +391,35 @@ impl Chunk {
 
                 let stone_max = (v - s - chunk_y * CHUNK_ISIZE).min(CHUNK_ISIZE);
                 for y in 0..stone_max {
-                    self.blocks[y as usize][z][x] = Some(Block {
-                        block_type: BlockType::Stone,
-                    });
+                    self.blocks[y as usize][z][x] = Some(Block::new(BlockType::Stone));
                 }
 
                 let dirt_max = (v - chunk_y * CHUNK_ISIZE).min(CHUNK_ISIZE);
                 for y in stone_max.max(0)..dirt_max {
-                    self.blocks[y as usize][z][x] = Some(Block {
-                        block_type: BlockType::Dirt,
-                    });
+                    self.blocks[y as usize][z][x] = Some(Block::new(BlockType::Dirt));
                 }
 
                 if (0..CHUNK_ISIZE).contains(&dirt_max) {
-                    self.blocks[dirt_max as usize][z][x] = Some(Block {
-                        block_type: BlockType::Grass,
-                    });
+                    self.blocks[dirt_max as usize][z][x] = Some(Block::new(BlockType::Grass));
+                }
+
+                // Plants only grow on the grass above the water
+                let plant_y = dirt_max + 1;
+                if (0..CHUNK_ISIZE).contains(&plant_y) && v + 1 >= SEA_LEVEL {
+                    let world_x = chunk_x * CHUNK_ISIZE + x as isize;
+                    let world_z = chunk_z * CHUNK_ISIZE + z as isize;
+                    if let Some(plant) = terrain_plant(world_x, world_z) {
+                        self.blocks[plant_y as usize][z][x] = Some(Block::new(plant));
+                    }
                 }
 
                 if chunk_y == 0 {
-                    self.blocks[0][z][x] = Some(Block {
-                        block_type: BlockType::Bedrock,
-                    });
+                    self.blocks[0][z][x] = Some(Block::new(BlockType::Bedrock));
                 }
-                if chunk_y < 128 / CHUNK_ISIZE {
+                if chunk_y < SEA_LEVEL / CHUNK_ISIZE {
                     for layer in self.blocks.iter_mut() {
                         if layer[z][x].is_none() {
-                            layer[z][x] = Some(Block {
-                                block_type: BlockType::Water,
-                            });
+                            layer[z][x] = Some(Block::new(BlockType::Water));
                         }
                     }
                 }
@@ -207,221 +427,452 @@ impl Chunk {
         }
     }
 
-    pub fn block_coords_to_local(
-        chunk_coords: Point3<isize>,
-        block_coords: Point3<isize>,
-    ) -> Option<Vector3<usize>> {
-        let chunk_position = chunk_coords * CHUNK_ISIZE;
-        let position = block_coords - chunk_position;
-        if (0..CHUNK_ISIZE).contains(&position.x)
-            && (0..CHUNK_ISIZE).contains(&position.y)
-            && (0..CHUNK_ISIZE).contains(&position.z)
+    /// Returns whether the face `face` of `block` is hidden by `neighbour`, the block on that
+    /// side of it.
+    fn is_face_hidden(
+        block: &Block,
+        neighbour: &Option<Block>,
+        face: FaceFlags,
+        settings: &Settings,
+    ) -> bool {
+        match neighbour {
+            Some(neighbour) => {
+                let opposite = match face {
+                    FACE_LEFT => FACE_RIGHT,
+                    FACE_RIGHT => FACE_LEFT,
+                    FACE_BOTTOM => FACE_TOP,
+                    FACE_TOP => FACE_BOTTOM,
+                    FACE_BACK => FACE_FRONT,
+                    _ => FACE_BACK,
+                };
+
+                // Cutout blocks have holes, so they don't hide anything behind them
+                block.block_type.is_transparent() == neighbour.block_type.is_transparent()
+                    && neighbour.block_type.render_class(settings) != RenderClass::Cutout
+                    && neighbour.block_type.shape().full_faces(neighbour.facing) & opposite
+                        != FACE_NONE
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the block next to the given side of the block at the given position, looking it
+    /// up in `border_blocks` if it's in a neighbouring chunk.
+    fn neighbour_block<'a>(
+        &'a self,
+        x: usize,
+        y: usize,
+        z: usize,
+        side: usize,
+        border_blocks: &'a BorderBlocks,
+    ) -> &'a Option<Block> {
+        let neighbour = Vector3::new(x as isize, y as isize, z as isize) + NEIGHBOURS[side];
+        if (0..CHUNK_ISIZE).contains(&neighbour.x)
+            && (0..CHUNK_ISIZE).contains(&neighbour.y)
+            && (0..CHUNK_ISIZE).contains(&neighbour.z)
         {
-            Some(position.cast().unwrap())
+            let neighbour = neighbour.map(|x| x as usize);
+            &self.blocks[neighbour.y][neighbour.z][neighbour.x]
         } else {
-            None
+            match side {
+                0 | 1 => &border_blocks[side][y][z],
+                2 | 3 => &border_blocks[side][y][x],
+                _ => &border_blocks[side][z][x],
+            }
         }
     }
 
-    #[rustfmt::skip]
-    fn check_visible_faces(&self, x: usize, y: usize, z: usize) -> FaceFlags {
-        let mut visible_faces = FACE_NONE;
-        let transparent = self.blocks[y][z][x].unwrap().block_type.is_transparent();
+    fn check_visible_faces(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+        border_blocks: &BorderBlocks,
+        settings: &Settings,
+    ) -> FaceFlags {
+        let block = &self.blocks[y][z][x].unwrap();
 
-        if x == 0 || self.blocks[y][z][x - 1].is_none()
-            || transparent != self.blocks[y][z][x - 1].unwrap().block_type.is_transparent()
-        {
-            visible_faces |= FACE_LEFT;
-        }
-        if x == CHUNK_SIZE - 1 || self.blocks[y][z][x + 1].is_none()
-            || transparent != self.blocks[y][z][x + 1].unwrap().block_type.is_transparent()
-        {
-            visible_faces |= FACE_RIGHT;
+        let mut visible_faces = FACE_NONE;
+        for (side, &face) in FACES.iter().enumerate() {
+            let neighbour = self.neighbour_block(x, y, z, side, border_blocks);
+            if !Self::is_face_hidden(block, neighbour, face, settings) {
+                visible_faces |= face;
+            }
         }
+        visible_faces
+    }
 
-        if y == 0 || self.blocks[y - 1][z][x].is_none()
-            || transparent != self.blocks[y - 1][z][x].unwrap().block_type.is_transparent()
-        {
-            visible_faces |= FACE_BOTTOM;
-        }
-        if y == CHUNK_SIZE - 1 || self.blocks[y + 1][z][x].is_none()
-            || transparent != self.blocks[y + 1][z][x].unwrap().block_type.is_transparent()
-        {
-            visible_faces |= FACE_TOP;
+    /// Returns the light next to each face of the block at the given position, in the same order
+    /// as `NEIGHBOURS`.
+    fn face_light(&self, x: usize, y: usize, z: usize, border_light: &BorderLight) -> [Light; 6] {
+        let mut light = [Light::default(); 6];
+        for (side, offset) in NEIGHBOURS.iter().enumerate() {
+            let neighbour = Vector3::new(x as isize, y as isize, z as isize) + offset;
+            light[side] = if (0..CHUNK_ISIZE).contains(&neighbour.x)
+                && (0..CHUNK_ISIZE).contains(&neighbour.y)
+                && (0..CHUNK_ISIZE).contains(&neighbour.z)
+            {
+                let neighbour = neighbour.map(|x| x as usize);
+                self.light[neighbour.y][neighbour.z][neighbour.x]
+            } else {
+                match side {
+                    0 | 1 => border_light[side][y][z],
+                    2 | 3 => border_light[side][y][x],
+                    _ => border_light[side][z][x],
+                }
+            };
         }
+        light
+    }
 
-        if z == 0 || self.blocks[y][z - 1][x].is_none()
-            || transparent != self.blocks[y][z - 1][x].unwrap().block_type.is_transparent()
-        {
-            visible_faces |= FACE_BACK;
-        }
-        if z == CHUNK_SIZE - 1 || self.blocks[y][z + 1][x].is_none()
-            || transparent != self.blocks[y][z + 1][x].unwrap().block_type.is_transparent()
+    /// Returns whether the block at the given position darkens the corners of the faces next to
+    /// it. Blocks just outside of the chunk are looked up in `border_occluders`.
+    fn is_occluding(&self, position: Vector3<isize>, border_occluders: &BorderOccluders) -> bool {
+        if (0..CHUNK_ISIZE).contains(&position.x)
+            && (0..CHUNK_ISIZE).contains(&position.y)
+            && (0..CHUNK_ISIZE).contains(&position.z)
         {
-            visible_faces |= FACE_FRONT;
+            self.blocks[position.y as usize][position.z as usize][position.x as usize]
+                .is_some_and(|block| block.block_type.is_opaque())
+        } else {
+            border_occluders.get(position)
         }
+    }
 
-        visible_faces
+    /// Returns the ambient occlusion of the corners of each visible face of the block at the given
+    /// position, in the same order as `Quad::ao`.
+    ///
+    /// Every corner is occluded by the two blocks next to it and the block diagonal to it, in front
+    /// of the face. A corner between two occluding blocks is fully occluded, regardless of the
+    /// diagonal block.
+    fn face_ao(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+        visible_faces: FaceFlags,
+        border_occluders: &BorderOccluders,
+    ) -> [[u8; 4]; 6] {
+        let position = Vector3::new(x as isize, y as isize, z as isize);
+
+        let mut ao = [[3; 4]; 6];
+        for (side, face) in FACES.iter().enumerate() {
+            if visible_faces & face == FACE_NONE {
+                continue;
+            }
+
+            let normal = NEIGHBOURS[side];
+            let front = position + normal;
+            for (corner, ao) in FACE_CORNERS[side].iter().zip(ao[side].iter_mut()) {
+                // The offsets towards the corner along the two axes of the face
+                let towards = corner.map(|x| x * 2 - 1);
+                let mut tangents = (0..3).filter(|&axis| normal[axis] == 0).map(|axis| {
+                    let mut tangent = Vector3::new(0, 0, 0);
+                    tangent[axis] = towards[axis];
+                    tangent
+                });
+                let u = tangents.next().unwrap();
+                let v = tangents.next().unwrap();
+
+                let side1 = self.is_occluding(front + u, border_occluders);
+                let side2 = self.is_occluding(front + v, border_occluders);
+                let diagonal = self.is_occluding(front + u + v, border_occluders);
+                *ao = if side1 && side2 {
+                    0
+                } else {
+                    3 - side1 as u8 - side2 as u8 - diagonal as u8
+                };
+            }
+        }
+        ao
     }
 
     fn cull_layer(
         &self,
         y: usize,
-    ) -> (
-        FxHashMap<(usize, usize), (BlockType, FaceFlags)>,
-        VecDeque<(usize, usize)>,
-    ) {
-        let mut culled = FxHashMap::default();
-        let mut queue = VecDeque::new();
+        border_blocks: &BorderBlocks,
+        border_light: &BorderLight,
+        border_occluders: &BorderOccluders,
+        settings: &Settings,
+    ) -> [[Option<CulledBlock>; CHUNK_SIZE]; CHUNK_SIZE] {
+        let mut culled = [[None; CHUNK_SIZE]; CHUNK_SIZE];
 
         let y_blocks = &self.blocks[y];
         for (z, z_blocks) in y_blocks.iter().enumerate() {
             for (x, block) in z_blocks.iter().enumerate() {
                 if let Some(block) = block {
                     // Don't add the block if it's not visible
-                    let visible_faces = self.check_visible_faces(x, y, z);
+                    let visible_faces = self.check_visible_faces(x, y, z, border_blocks, settings);
                     if visible_faces == FACE_NONE {
                         continue;
                     }
 
-                    culled.insert((x, z), (block.block_type, visible_faces));
-                    queue.push_back((x, z));
+                    culled[z][x] = Some(CulledBlock {
+                        block: *block,
+                        visible_faces,
+                        light: self.face_light(x, y, z, border_light),
+                        ao: self.face_ao(x, y, z, visible_faces, border_occluders),
+                    });
                 }
             }
         }
 
-        (culled, queue)
+        culled
     }
 
-    fn layer_to_quads(
+    /// Returns whether the faces of the block are merged with the faces next to them by
+    /// `slice_to_quads`, instead of being turned into a quad of their own.
+    fn is_merged(block_type: BlockType) -> bool {
+        // Water can be lower than a full block, and the faces of other shapes don't line up
+        block_type.shape() == BlockShape::Cube && block_type != BlockType::Water
+    }
+
+    /// Returns the position of a face in a slice of the chunk.
+    ///
+    /// The slices of each side are perpendicular to its normal, `depth` being the position along
+    /// the normal. `u` and `v` are the positions along the X and Y axes of the left and right
+    /// sides, the X and Y axes of the back and front sides, and the X and Z axes of the bottom and
+    /// top sides.
+    fn slice_position(side: usize, depth: usize, u: usize, v: usize) -> Vector3<usize> {
+        match side {
+            0 | 1 => Vector3::new(depth, v, u),
+            2 | 3 => Vector3::new(u, v, depth),
+            _ => Vector3::new(u, depth, v),
+        }
+    }
+
+    /// Turns a block that isn't merged with the blocks next to it into a quad with all of its
+    /// visible faces.
+    fn block_quad(
         &self,
-        y: usize,
+        position: Vector3<usize>,
         offset: Point3<isize>,
-        culled: FxHashMap<(usize, usize), (BlockType, FaceFlags)>,
-        queue: &mut VecDeque<(usize, usize)>,
-        highlighted: Option<(Vector3<usize>, Vector3<i32>)>,
-    ) -> Vec<Quad> {
-        let mut quads: Vec<Quad> = Vec::new();
-        let mut visited = FxHashSet::default();
-        let hl = highlighted.map(|h| h.0);
-        while let Some((x, z)) = queue.pop_front() {
-            let position = offset + Vector3::new(x, y, z).cast().unwrap();
+        culled_block: CulledBlock,
+        border_blocks: &BorderBlocks,
+    ) -> Quad {
+        let CulledBlock {
+            block,
+            visible_faces,
+            light,
+            ao,
+        } = culled_block;
 
-            if visited.contains(&(x, z)) {
-                continue;
+        let mut quad = Quad::new(offset + position.cast().unwrap(), 1, 1);
+        quad.visible_faces = visible_faces;
+        quad.block_type = Some(block.block_type);
+        quad.facing = block.facing;
+        quad.light = light;
+        quad.ao = ao;
+
+        if block.block_type == BlockType::Water {
+            let (x, y, z) = (position.x, position.y, position.z);
+            let above = self.neighbour_block(x, y, z, 5, border_blocks);
+            let water_above = above.map(|block| block.block_type) == Some(BlockType::Water);
+            quad.height = fluid::surface_height(block.level, water_above);
+            if quad.height < 1.0 {
+                // The lowered surface can't be hidden by the block above
+                quad.visible_faces |= FACE_TOP;
             }
-            visited.insert((x, z));
+        }
 
-            if let Some(&(block_type, visible_faces)) = &culled.get(&(x, z)) {
-                let mut quad_faces = visible_faces;
+        quad
+    }
 
-                if hl == Some(Vector3::new(x, y, z)) {
-                    let mut quad = Quad::new(position, 1, 1);
-                    quad.highlighted_normal = highlighted.unwrap().1;
-                    quad.visible_faces = quad_faces;
-                    quad.block_type = Some(block_type);
-                    quads.push(quad);
-                    continue;
+    /// Greedily merges the faces facing `side` in the slice at `depth` into as few quads as
+    /// possible.
+    fn slice_to_quads(
+        culled: &[[[Option<CulledBlock>; CHUNK_SIZE]; CHUNK_SIZE]],
+        side: usize,
+        depth: usize,
+        offset: Point3<isize>,
+        settings: &Settings,
+    ) -> Vec<Quad> {
+        let mut mask = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+        for (v, row) in mask.iter_mut().enumerate() {
+            for (u, face) in row.iter_mut().enumerate() {
+                let position = Self::slice_position(side, depth, u, v);
+                if let Some(culled_block) = culled[position.y][position.z][position.x] {
+                    let block_type = culled_block.block.block_type;
+                    if culled_block.visible_faces & FACES[side] != FACE_NONE
+                        && Self::is_merged(block_type)
+                    {
+                        *face = Some(MergeableFace {
+                            block_type,
+                            light: culled_block.light[side],
+                            ao: culled_block.ao[side],
+                        });
+                    }
                 }
+            }
+        }
 
-                if block_type == BlockType::Water {
-                    let mut quad = Quad::new(position, 1, 1);
-                    quad.visible_faces = quad_faces;
-                    quad.block_type = Some(block_type);
-                    quads.push(quad);
-                    continue;
-                }
+        let mut quads = Vec::new();
+        for v in 0..CHUNK_SIZE {
+            let mut u = 0;
+            while u < CHUNK_SIZE {
+                let face = match mask[v][u] {
+                    Some(face) => face,
+                    None => {
+                        u += 1;
+                        continue;
+                    }
+                };
+                let can_merge = |other: Option<MergeableFace>| {
+                    other.is_some_and(|other| face.can_merge_with(&other, side, settings))
+                };
 
-                // Extend along the X axis
-                let mut xmax = x + 1;
-                for x_ in x..CHUNK_SIZE {
-                    xmax = x_ + 1;
+                // Extend along the U axis
+                let mut width = 1;
+                while u + width < CHUNK_SIZE && can_merge(mask[v][u + width]) {
+                    width += 1;
+                }
 
-                    if visited.contains(&(xmax, z)) || hl == Some(Vector3::new(xmax, y, z)) {
-                        break;
-                    }
+                // Extend along the V axis
+                let mut height = 1;
+                while v + height < CHUNK_SIZE
+                    && mask[v + height][u..u + width]
+                        .iter()
+                        .all(|&other| can_merge(other))
+                {
+                    height += 1;
+                }
 
-                    if let Some(&(block_type_, visible_faces_)) = culled.get(&(xmax, z)) {
-                        quad_faces |= visible_faces_;
-                        if block_type != block_type_ {
-                            break;
-                        }
-                    } else {
-                        break;
+                for row in &mut mask[v..v + height] {
+                    for other in &mut row[u..u + width] {
+                        *other = None;
                     }
+                }
 
-                    visited.insert((xmax, z));
+                let position = offset + Self::slice_position(side, depth, u, v).cast().unwrap();
+                let (width, height) = (width as isize, height as isize);
+                let mut quad = match side {
+                    0 | 1 => Quad::new(position, 1, width),
+                    2 | 3 => Quad::new(position, width, 1),
+                    _ => Quad::new(position, width, height),
+                };
+                if side < 4 {
+                    quad.height = height as f32;
                 }
+                quad.visible_faces = FACES[side];
+                quad.block_type = Some(face.block_type);
+                quad.light[side] = face.light;
+                quad.ao[side] = face.ao;
+                quads.push(quad);
 
-                // Extend along the Z axis
-                let mut zmax = z + 1;
-                'z: for z_ in z..CHUNK_SIZE {
-                    zmax = z_ + 1;
+                u += width as usize;
+            }
+        }
 
-                    for x_ in x..xmax {
-                        if visited.contains(&(x_, zmax)) || hl == Some(Vector3::new(x_, y, zmax)) {
-                            break 'z;
-                        }
+        quads
+    }
 
-                        if let Some(&(block_type_, visible_faces_)) = culled.get(&(x_, zmax)) {
-                            quad_faces |= visible_faces_;
-                            if block_type != block_type_ {
-                                break 'z;
-                            }
-                        } else {
-                            break 'z;
-                        }
-                    }
+    /// Converts the chunk to quads, merging the faces of cubes facing the same direction where
+    /// possible.
+    pub fn to_quads(
+        &self,
+        chunk_coords: Point3<isize>,
+        border_blocks: &BorderBlocks,
+        border_light: &BorderLight,
+        border_occluders: &BorderOccluders,
+        settings: &Settings,
+    ) -> Vec<Quad> {
+        let offset = chunk_coords * CHUNK_ISIZE;
 
-                    for x_ in x..xmax {
-                        visited.insert((x_, zmax));
+        let culled: Vec<_> = (0..CHUNK_SIZE)
+            .into_par_iter()
+            .map(|y| self.cull_layer(y, border_blocks, border_light, border_occluders, settings))
+            .collect();
+
+        let mut quads = Vec::new();
+        for (y, layer) in culled.iter().enumerate() {
+            for (z, row) in layer.iter().enumerate() {
+                for (x, culled_block) in row.iter().enumerate() {
+                    if let Some(culled_block) = *culled_block {
+                        if !Self::is_merged(culled_block.block.block_type) {
+                            let position = Vector3::new(x, y, z);
+                            quads.push(self.block_quad(
+                                position,
+                                offset,
+                                culled_block,
+                                border_blocks,
+                            ));
+                        }
                     }
                 }
-
-                let mut quad = Quad::new(position, (xmax - x) as isize, (zmax - z) as isize);
-                quad.visible_faces = quad_faces;
-                quad.block_type = Some(block_type);
-                quads.push(quad);
             }
         }
 
+        let merged_quads: Vec<Quad> = (0..6 * CHUNK_SIZE)
+            .into_par_iter()
+            .flat_map(|slice| {
+                let (side, depth) = (slice / CHUNK_SIZE, slice % CHUNK_SIZE);
+                Self::slice_to_quads(&culled, side, depth, offset, settings)
+            })
+            .collect();
+        quads.extend(merged_quads);
+
         quads
     }
 
-    fn quads_to_geometry(quads: Vec<Quad>) -> Geometry<BlockVertex, u16> {
-        let mut geometry: Geometry<BlockVertex, u16> = Default::default();
-        for quad in quads {
-            geometry.append(&mut quad.to_geometry(geometry.vertices.len() as u16));
-        }
-        geometry
+    /// Returns a copy of the blocks and light of the chunk, without any buffers, that can be
+    /// meshed on another thread.
+    pub fn snapshot(&self) -> Box<Chunk> {
+        let mut snapshot = Box::new(Chunk::default());
+        snapshot.blocks = self.blocks;
+        snapshot.light = self.light;
+        snapshot
     }
 
-    pub fn update_geometry(
-        &mut self,
-        render_context: &RenderContext,
+    /// Converts the chunk to geometry. This doesn't touch the GPU, so it can run on any thread.
+    pub fn mesh(
+        &self,
         chunk_coords: Point3<isize>,
-        highlighted: Option<(Point3<isize>, Vector3<i32>)>,
-    ) {
-        let highlighted = highlighted.and_then(|(position, normal)| {
-            Self::block_coords_to_local(chunk_coords, position).map(|x| (x, normal))
-        });
+        border_blocks: &BorderBlocks,
+        border_light: &BorderLight,
+        border_occluders: &BorderOccluders,
+        settings: &Settings,
+    ) -> ChunkMesh {
+        let quads = self.to_quads(
+            chunk_coords,
+            border_blocks,
+            border_light,
+            border_occluders,
+            settings,
+        );
+        ChunkMesh::from_quads(quads, settings)
+    }
 
-        let offset = chunk_coords * CHUNK_ISIZE;
-        let quads: Vec<Quad> = (0..CHUNK_SIZE)
-            .into_par_iter()
-            .flat_map(|y| {
-                let (culled, mut queue) = self.cull_layer(y);
-                self.layer_to_quads(y, offset, culled, &mut queue, highlighted)
-            })
-            .collect();
+    /// Converts the chunk to a single geometry as if it had no neighbours (see
+    /// `isolated_borders`), at the origin of the world.
+    ///
+    /// This only needs the blocks of the chunk, so meshing can be tested and benchmarked without a
+    /// world or a GPU.
+    pub fn mesh_isolated(&self, settings: &Settings) -> Geometry<BlockVertex, u32> {
+        let (border_blocks, border_light, border_occluders) = isolated_borders();
+        self.mesh(
+            Point3::new(0, 0, 0),
+            &border_blocks,
+            &border_light,
+            &border_occluders,
+            settings,
+        )
+        .to_geometry()
+    }
 
-        self.buffers = Some(GeometryBuffers::from_geometry(
-            render_context,
-            &Self::quads_to_geometry(quads),
-            BufferUsage::empty(),
-        ));
+    /// Uploads a packed mesh made by `mesh` to the `ChunkArena`, replacing the current buffers of
+    /// the chunk.
+    pub fn upload_mesh(
+        &mut self,
+        render_context: &RenderContext,
+        chunk_arena: &mut ChunkArena,
+        mesh: &ChunkMesh<ChunkVertex>,
+    ) {
+        self.buffers = Some(chunk_arena.upload(render_context, mesh));
+    }
 
-        self.update_fullness();
+    /// Returns the size of the buffers of the chunk, in bytes.
+    pub fn buffer_size(&self) -> wgpu::BufferAddress {
+        self.buffers.as_ref().map_or(0, ChunkBuffers::size)
     }
 
     pub fn save(&self, position: Point3<isize>, store: &sled::Db) -> anyhow::Result<()> {
@@ -443,7 +894,8 @@ impl Chunk {
         }
     }
 
-    pub fn is_visible(&self, position: Point3<isize>, view: &View) -> bool {
+    /// Returns whether the chunk with its origin at `position` is inside the view.
+    pub fn is_visible(position: Point3<isize>, view: &View) -> bool {
         let aabb = Aabb {
             min: position.cast().unwrap(),
             max: (position + Vector3::new(CHUNK_ISIZE, CHUNK_ISIZE, CHUNK_ISIZE))
@@ -451,6 +903,323 @@ impl Chunk {
                 .unwrap(),
         };
 
-        aabb.intersects(&view.frustrum_aabb)
+        view.is_visible(&aabb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fxhash::{FxHashMap, FxHashSet};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Meshes the chunk without merging any faces, i.e. every block with a visible face becomes
+    /// a quad of its own.
+    fn unmerged_quads(chunk: &Chunk, settings: &Settings) -> Vec<Quad> {
+        let (border_blocks, border_light, border_occluders) = isolated_borders();
+        let mut quads = Vec::new();
+        for y in 0..CHUNK_SIZE {
+            let culled = chunk.cull_layer(
+                y,
+                &border_blocks,
+                &border_light,
+                &border_occluders,
+                settings,
+            );
+            for (z, row) in culled.iter().enumerate() {
+                for (x, culled_block) in row.iter().enumerate() {
+                    if let Some(culled_block) = *culled_block {
+                        let position = Vector3::new(x, y, z);
+                        quads.push(chunk.block_quad(
+                            position,
+                            Point3::new(0, 0, 0),
+                            culled_block,
+                            &border_blocks,
+                        ));
+                    }
+                }
+            }
+        }
+        quads
+    }
+
+    fn greedy_quads(chunk: &Chunk, settings: &Settings) -> Vec<Quad> {
+        let (border_blocks, border_light, border_occluders) = isolated_borders();
+        chunk.to_quads(
+            Point3::new(0, 0, 0),
+            &border_blocks,
+            &border_light,
+            &border_occluders,
+            settings,
+        )
+    }
+
+    fn triangle_count(quads: &[Quad]) -> usize {
+        quads
+            .iter()
+            .map(|quad| quad.to_geometry(0).indices.len() / 3)
+            .sum()
+    }
+
+    /// Returns the faces of the blocks the quads cover, panicking if a face is covered twice.
+    fn covered_faces(quads: &[Quad]) -> FxHashSet<(usize, Point3<isize>)> {
+        let mut faces = FxHashSet::default();
+        for quad in quads {
+            for (side, &face) in FACES.iter().enumerate() {
+                if quad.visible_faces & face == FACE_NONE {
+                    continue;
+                }
+
+                for (x, y, z) in
+                    itertools::iproduct!(0..quad.dx, 0..quad.height as isize, 0..quad.dz)
+                {
+                    let position = quad.position + Vector3::new(x, y, z);
+                    assert!(
+                        faces.insert((side, position)),
+                        "face {} of {:?} is covered twice",
+                        side,
+                        position
+                    );
+                }
+            }
+        }
+        faces
+    }
+
+    fn random_chunk(seed: u64) -> Chunk {
+        let block_types = [
+            BlockType::Stone,
+            BlockType::Dirt,
+            BlockType::Grass,
+            BlockType::Cobblestone,
+        ];
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut chunk = Chunk::default();
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    // Denser towards the bottom, like terrain
+                    if rng.gen_range(0, CHUNK_SIZE) >= y {
+                        let block_type = block_types[rng.gen_range(0, block_types.len())];
+                        chunk.blocks[y][z][x] = Some(Block::new(block_type));
+                    }
+                }
+            }
+        }
+        chunk
+    }
+
+    fn checkerboard_chunk() -> Chunk {
+        let mut chunk = Chunk::default();
+        for (x, y, z) in itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+            if (x + y + z) % 2 == 0 {
+                chunk.blocks[y][z][x] = Some(Block::new(BlockType::Stone));
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn flat_terrain_is_one_quad_per_side() {
+        let mut chunk = Chunk::default();
+        for layer in &mut chunk.blocks[..CHUNK_SIZE / 2] {
+            *layer = [[Some(Block::new(BlockType::Stone)); CHUNK_SIZE]; CHUNK_SIZE];
+        }
+
+        let settings = Settings::default();
+        let greedy = greedy_quads(&chunk, &settings);
+        let unmerged = unmerged_quads(&chunk, &settings);
+        assert_eq!(greedy.len(), 6);
+        assert_eq!(triangle_count(&greedy), 12);
+        assert_eq!(covered_faces(&greedy), covered_faces(&unmerged));
+    }
+
+    #[test]
+    fn greedy_mesh_has_fewer_triangles() {
+        let settings = Settings::default();
+        for seed in 0..4 {
+            let chunk = random_chunk(seed);
+            let greedy = triangle_count(&greedy_quads(&chunk, &settings));
+            let unmerged = triangle_count(&unmerged_quads(&chunk, &settings));
+            assert!(
+                greedy < unmerged,
+                "{} triangles after merging, {} before",
+                greedy,
+                unmerged
+            );
+        }
+    }
+
+    #[test]
+    fn greedy_mesh_is_watertight() {
+        let settings = Settings::default();
+        for seed in 0..4 {
+            let chunk = random_chunk(seed);
+            let greedy = covered_faces(&greedy_quads(&chunk, &settings));
+            let unmerged = covered_faces(&unmerged_quads(&chunk, &settings));
+            assert_eq!(greedy, unmerged);
+        }
+    }
+
+    #[test]
+    fn checkerboard_is_split_into_sub_meshes() {
+        let chunk = checkerboard_chunk();
+        let settings = Settings::default();
+        let (border_blocks, border_light, border_occluders) = isolated_borders();
+        let mesh = chunk.mesh(
+            Point3::new(0, 0, 0),
+            &border_blocks,
+            &border_light,
+            &border_occluders,
+            &settings,
+        );
+
+        // Every block of the checkerboard has 6 visible faces, none of which can be merged
+        let vertex_count: usize = mesh.opaque.iter().map(|g| g.vertices.len()).sum();
+        assert!(vertex_count > MAX_SUB_MESH_VERTICES);
+        assert!(mesh.opaque.len() > 1);
+
+        for geometry in &mesh.opaque {
+            assert!(geometry.vertices.len() <= MAX_SUB_MESH_VERTICES);
+            assert!(geometry
+                .indices
+                .iter()
+                .all(|&index| (index as usize) < geometry.vertices.len()));
+        }
+
+        let triangle_count: usize = mesh.opaque.iter().map(|g| g.index_count() / 3).sum();
+        assert_eq!(triangle_count, CHUNK_SIZE.pow(3) / 2 * 6 * 2);
+    }
+
+    #[test]
+    fn water_under_water_in_chunk_above_is_full_height() {
+        let flowing = Block {
+            level: 3,
+            ..Block::new(BlockType::Water)
+        };
+        let mut chunk = Chunk::default();
+        chunk.blocks[CHUNK_SIZE - 1][0][0] = Some(flowing);
+
+        let settings = Settings::default();
+        let (mut border_blocks, border_light, border_occluders) = isolated_borders();
+        let height = |border_blocks: &BorderBlocks| {
+            let quads = chunk.to_quads(
+                Point3::new(0, 0, 0),
+                border_blocks,
+                &border_light,
+                &border_occluders,
+                &settings,
+            );
+            quads[0].height
+        };
+        assert!(height(&border_blocks) < 1.0);
+
+        border_blocks[5][0][0] = Some(flowing);
+        assert_eq!(height(&border_blocks), 1.0);
+    }
+
+    #[test]
+    fn generated_plants_grow_on_grass() {
+        let mut plants = 0;
+        for (chunk_x, chunk_z) in itertools::iproduct!(0..2, 0..2) {
+            // The chunks the terrain goes through around the sea level
+            let mut below = Chunk::default();
+            below.generate(chunk_x, SEA_LEVEL / CHUNK_ISIZE - 1, chunk_z);
+            let mut above = Chunk::default();
+            above.generate(chunk_x, SEA_LEVEL / CHUNK_ISIZE, chunk_z);
+
+            for (y, z, x) in itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+                for (chunk, chunk_below) in [(&below, None), (&above, Some(&below))] {
+                    let block_type = chunk.blocks[y][z][x].map(|block| block.block_type);
+                    if !matches!(block_type, Some(BlockType::TallGrass | BlockType::Poppy)) {
+                        continue;
+                    }
+
+                    let ground = match (y, chunk_below) {
+                        (0, Some(chunk_below)) => chunk_below.blocks[CHUNK_SIZE - 1][z][x],
+                        (0, None) => continue,
+                        _ => chunk.blocks[y - 1][z][x],
+                    };
+                    assert_eq!(ground.map(|block| block.block_type), Some(BlockType::Grass));
+                    plants += 1;
+                }
+            }
+        }
+        assert!(plants > 0);
+    }
+
+    #[test]
+    fn ambient_occlusion_matches_across_chunk_border() {
+        // A floor across two chunks next to each other, with a block on it in the first chunk
+        // that is diagonal to the corner of a face at the edge of the second one
+        let mut chunks = [Chunk::default(), Chunk::default()];
+        for chunk in &mut chunks {
+            for (x, z) in itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+                chunk.blocks[0][z][x] = Some(Block::new(BlockType::Stone));
+            }
+        }
+        chunks[0].blocks[1][5][CHUNK_SIZE - 1] = Some(Block::new(BlockType::Stone));
+
+        let get = |position: Point3<isize>| {
+            if !(0..2 * CHUNK_ISIZE).contains(&position.x)
+                || !(0..CHUNK_ISIZE).contains(&position.y)
+                || !(0..CHUNK_ISIZE).contains(&position.z)
+            {
+                return None;
+            }
+            let (index, x) = (position.x / CHUNK_ISIZE, position.x % CHUNK_ISIZE);
+            chunks[index as usize].blocks[position.y as usize][position.z as usize][x as usize]
+        };
+
+        // The ambient occlusion of the corners of the top faces on the border, by their position
+        let settings = Settings::default();
+        let mut border_corners = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let chunk_coords = Point3::new(index as isize, 0, 0);
+            let origin = chunk_coords * CHUNK_ISIZE;
+            let (mut border_blocks, border_light, _) = isolated_borders();
+            for (y, z) in itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+                let (y, z) = (y as isize, z as isize);
+                border_blocks[0][y as usize][z as usize] = get(origin + Vector3::new(-1, y, z));
+                border_blocks[1][y as usize][z as usize] =
+                    get(origin + Vector3::new(CHUNK_ISIZE, y, z));
+            }
+            let border_occluders = BorderOccluders::from_fn(|position| {
+                get(origin + position).is_some_and(|block| block.block_type.is_opaque())
+            });
+
+            let mesh = chunk.mesh(
+                chunk_coords,
+                &border_blocks,
+                &border_light,
+                &border_occluders,
+                &settings,
+            );
+            let corners: FxHashMap<_, _> = mesh
+                .to_geometry()
+                .vertices
+                .iter()
+                .filter(|vertex| {
+                    vertex.position[0] == CHUNK_SIZE as f32 && vertex.normal == [0.0, 1.0, 0.0]
+                })
+                .map(|vertex| {
+                    let position = (vertex.position[1] as isize, vertex.position[2] as isize);
+                    (position, vertex.ambient_occlusion)
+                })
+                .collect();
+            border_corners.push(corners);
+        }
+
+        for (position, ao) in &border_corners[0] {
+            if let Some(other_ao) = border_corners[1].get(position) {
+                assert_eq!(ao, other_ao, "corner at y, z = {:?}", position);
+            }
+        }
+        // Without the block in the first chunk, the face in the second one would be merged
+        // with its neighbours, leaving no corner there
+        let occluded = border_corners[0][&(1, 5)];
+        assert_eq!(border_corners[1].get(&(1, 5)), Some(&occluded));
+        assert!(occluded < border_corners[1][&(1, 0)]);
     }
 }
@@ -0,0 +1,287 @@
+use std::{mem::size_of, ops::Range};
+
+use cgmath::Point3;
+use wgpu::{Buffer, BufferAddress, BufferUsage, RenderPass};
+
+use crate::{
+    buffer_arena::{Allocation, BufferArena},
+    geometry::Geometry,
+    render_context::RenderContext,
+    vertex::{ChunkInstance, ChunkVertex},
+    world::{
+        block::RenderClass,
+        chunk::{ChunkMesh, CHUNK_ISIZE},
+    },
+};
+
+/// The initial sizes of the arenas, which grow as needed.
+const VERTEX_ARENA_SIZE: BufferAddress = 64 * 1024 * 1024;
+const INDEX_ARENA_SIZE: BufferAddress = 32 * 1024 * 1024;
+
+/// An indirect draw command, in the layout `multi_draw_indexed_indirect` reads.
+// Only the GPU reads the fields, from the draw buffer
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    base_index: u32,
+    vertex_offset: i32,
+    base_instance: u32,
+}
+
+/// A sub-mesh of a chunk in the `ChunkArena`.
+pub struct SubMesh {
+    vertices: Allocation,
+    indices: Allocation,
+    index_count: u32,
+}
+
+/// A packed `ChunkMesh` uploaded to the `ChunkArena`. Its space in the arena is freed when it's
+/// dropped.
+pub struct ChunkBuffers {
+    pub opaque: Vec<SubMesh>,
+    pub cutout: Vec<SubMesh>,
+    pub translucent: Vec<SubMesh>,
+}
+
+impl ChunkBuffers {
+    /// Returns the combined size of the vertices and indices of the chunk, in bytes.
+    pub fn size(&self) -> BufferAddress {
+        [&self.opaque, &self.cutout, &self.translucent]
+            .iter()
+            .flat_map(|sub_meshes| sub_meshes.iter())
+            .map(|sub_mesh| sub_mesh.vertices.size + sub_mesh.indices.size)
+            .sum()
+    }
+
+    /// Returns the sub-meshes of the given `RenderClass`.
+    pub fn sub_meshes(&self, render_class: RenderClass) -> &[SubMesh] {
+        match render_class {
+            RenderClass::Opaque => &self.opaque,
+            RenderClass::Cutout => &self.cutout,
+            RenderClass::Translucent => &self.translucent,
+        }
+    }
+}
+
+/// The vertices and indices of every chunk mesh, sub-allocated from a few large buffers, so the
+/// chunks can be drawn without switching buffers or bind groups in between.
+///
+/// The draws of a frame are collected with `push`, and written to the GPU with `write_draws`.
+/// If the device has multi-draw-indirect enabled, each batch of draws is then made with a single
+/// `multi_draw_indexed_indirect` call, and otherwise with a call per sub-mesh. The offset of the
+/// chunk of a draw is read from the instance at its `base_instance`, so the device must only
+/// enable it where indirect draws honour that.
+pub struct ChunkArena {
+    vertices: BufferArena,
+    indices: BufferArena,
+    /// Whether the device supports `multi_draw_indexed_indirect`.
+    multi_draw: bool,
+
+    /// The draws of the current frame, and the instance with the offset of the chunk of each.
+    draws: Vec<DrawIndexedIndirect>,
+    instances: Vec<ChunkInstance>,
+    /// The number of draws the buffers of the draws have room for.
+    draw_capacity: usize,
+    draw_buffer: Buffer,
+    instance_buffer: Buffer,
+}
+
+impl ChunkArena {
+    pub fn new(render_context: &RenderContext) -> Self {
+        let vertices = BufferArena::new(
+            render_context,
+            "chunk_vertex_arena",
+            BufferUsage::VERTEX,
+            size_of::<ChunkVertex>() as BufferAddress,
+            VERTEX_ARENA_SIZE,
+        );
+        let indices = BufferArena::new(
+            render_context,
+            "chunk_index_arena",
+            BufferUsage::INDEX,
+            wgpu::COPY_BUFFER_ALIGNMENT,
+            INDEX_ARENA_SIZE,
+        );
+
+        let multi_draw = render_context
+            .device
+            .features()
+            .contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+
+        let draw_capacity = 1024;
+        let (draw_buffer, instance_buffer) =
+            Self::create_draw_buffers(render_context, draw_capacity);
+
+        Self {
+            vertices,
+            indices,
+            multi_draw,
+
+            draws: Vec::new(),
+            instances: Vec::new(),
+            draw_capacity,
+            draw_buffer,
+            instance_buffer,
+        }
+    }
+
+    fn create_draw_buffers(render_context: &RenderContext, capacity: usize) -> (Buffer, Buffer) {
+        let create_buffer = |label, size, usage| {
+            render_context
+                .device
+                .create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size: (capacity * size) as BufferAddress,
+                    usage: usage | BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                })
+        };
+
+        (
+            create_buffer(
+                "chunk_draw_buffer",
+                size_of::<DrawIndexedIndirect>(),
+                BufferUsage::INDIRECT,
+            ),
+            create_buffer(
+                "chunk_instance_buffer",
+                size_of::<ChunkInstance>(),
+                BufferUsage::VERTEX,
+            ),
+        )
+    }
+
+    /// Returns the number of bytes in use by chunk meshes, and the combined size of the arenas.
+    pub fn usage(&self) -> (BufferAddress, BufferAddress) {
+        (
+            self.vertices.used() + self.indices.used(),
+            self.vertices.capacity + self.indices.capacity,
+        )
+    }
+
+    /// Returns whether the chunks are drawn with `multi_draw_indexed_indirect`, or with a call
+    /// per sub-mesh.
+    pub fn multi_draw(&self) -> bool {
+        self.multi_draw
+    }
+
+    /// Copies a packed mesh into the arena.
+    pub fn upload(
+        &mut self,
+        render_context: &RenderContext,
+        mesh: &ChunkMesh<ChunkVertex>,
+    ) -> ChunkBuffers {
+        let mut upload = |sub_meshes: &[Geometry<ChunkVertex, u16>]| {
+            sub_meshes
+                .iter()
+                .map(|geometry| SubMesh {
+                    vertices: self
+                        .vertices
+                        .allocate(render_context, bytemuck::cast_slice(&geometry.vertices)),
+                    indices: self
+                        .indices
+                        .allocate(render_context, bytemuck::cast_slice(&geometry.indices)),
+                    index_count: geometry.indices.len() as u32,
+                })
+                .collect()
+        };
+
+        ChunkBuffers {
+            opaque: upload(&mesh.opaque),
+            cutout: upload(&mesh.cutout),
+            translucent: upload(&mesh.translucent),
+        }
+    }
+
+    /// Removes the draws of the last frame.
+    pub fn clear_draws(&mut self) {
+        self.draws.clear();
+        self.instances.clear();
+    }
+
+    /// Returns the number of draws pushed this frame, which is the index of the next draw.
+    pub fn draw_count(&self) -> u32 {
+        self.draws.len() as u32
+    }
+
+    /// Adds draws of every sub-mesh of the chunk at `chunk_coords` of the given `RenderClass`.
+    ///
+    /// Returns the number of triangles that will be drawn.
+    pub fn push(
+        &mut self,
+        buffers: &ChunkBuffers,
+        chunk_coords: Point3<isize>,
+        render_class: RenderClass,
+    ) -> usize {
+        let offset = (chunk_coords * CHUNK_ISIZE).cast::<f32>().unwrap();
+        let mut triangle_count = 0;
+        for sub_mesh in buffers.sub_meshes(render_class) {
+            let vertices = self.vertices.range(&sub_mesh.vertices);
+            let indices = self.indices.range(&sub_mesh.indices);
+            self.draws.push(DrawIndexedIndirect {
+                index_count: sub_mesh.index_count,
+                instance_count: 1,
+                base_index: (indices.start / size_of::<u16>() as BufferAddress) as u32,
+                vertex_offset: (vertices.start / size_of::<ChunkVertex>() as BufferAddress) as i32,
+                base_instance: self.instances.len() as u32,
+            });
+            self.instances.push(ChunkInstance {
+                offset: offset.into(),
+            });
+            triangle_count += sub_mesh.index_count as usize / 3;
+        }
+        triangle_count
+    }
+
+    /// Writes the draws of this frame to the GPU, growing the buffers of the draws if needed.
+    pub fn write_draws(&mut self, render_context: &RenderContext) {
+        if self.draws.is_empty() {
+            return;
+        }
+        if self.draws.len() > self.draw_capacity {
+            self.draw_capacity = self.draws.len().next_power_of_two();
+            let (draw_buffer, instance_buffer) =
+                Self::create_draw_buffers(render_context, self.draw_capacity);
+            self.draw_buffer = draw_buffer;
+            self.instance_buffer = instance_buffer;
+        }
+
+        let queue = &render_context.queue;
+        queue.write_buffer(&self.draw_buffer, 0, bytemuck::cast_slice(&self.draws));
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&self.instances),
+        );
+    }
+
+    /// Makes the given range of the draws of this frame, with the pipeline that's already set.
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, draws: Range<u32>) {
+        if draws.start == draws.end {
+            return;
+        }
+
+        render_pass.set_vertex_buffer(0, self.vertices.buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.indices.buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        if self.multi_draw {
+            render_pass.multi_draw_indexed_indirect(
+                &self.draw_buffer,
+                (draws.start as usize * size_of::<DrawIndexedIndirect>()) as BufferAddress,
+                draws.end - draws.start,
+            );
+        } else {
+            for draw in &self.draws[draws.start as usize..draws.end as usize] {
+                render_pass.draw_indexed(
+                    draw.base_index..draw.base_index + draw.index_count,
+                    draw.vertex_offset,
+                    draw.base_instance..draw.base_instance + 1,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use cgmath::Point3;
+
+use crate::{
+    settings::Settings,
+    vertex::ChunkVertex,
+    world::{
+        chunk::{BorderBlocks, BorderOccluders, Chunk, ChunkMesh},
+        light::BorderLight,
+        visibility::ChunkVisibility,
+    },
+};
+
+/// A mesh made by a worker thread, waiting to be uploaded to the GPU.
+pub struct MeshResult {
+    pub position: Point3<isize>,
+    /// The `Chunk::mesh_version` of the chunk when it was queued for meshing.
+    pub version: u64,
+    pub mesh: ChunkMesh<ChunkVertex>,
+    pub visibility: ChunkVisibility,
+}
+
+/// Meshes chunks on rayon's worker threads, so the main thread only has to upload the results.
+pub struct ChunkMesher {
+    sender: Sender<MeshResult>,
+    receiver: Receiver<MeshResult>,
+    /// The version given to the next chunk queued for meshing.
+    next_version: u64,
+}
+
+impl Default for ChunkMesher {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            next_version: 1,
+        }
+    }
+}
+
+impl ChunkMesher {
+    /// Starts meshing a snapshot of a chunk on a worker thread.
+    ///
+    /// Returns the version of the mesh, which the chunk has to store to be able to tell whether
+    /// the result is stale when it arrives.
+    pub fn spawn(
+        &mut self,
+        position: Point3<isize>,
+        snapshot: Box<Chunk>,
+        border_blocks: Box<BorderBlocks>,
+        border_light: Box<BorderLight>,
+        border_occluders: BorderOccluders,
+        settings: Settings,
+    ) -> u64 {
+        let version = self.next_version;
+        self.next_version += 1;
+
+        let sender = self.sender.clone();
+        rayon::spawn(move || {
+            let mesh = snapshot
+                .mesh(
+                    position,
+                    &border_blocks,
+                    &border_light,
+                    &border_occluders,
+                    &settings,
+                )
+                .pack(position);
+            let visibility = ChunkVisibility::compute(&snapshot, &settings);
+            // The receiver is only gone when the world is, in which case the mesh isn't needed
+            let _ = sender.send(MeshResult {
+                position,
+                version,
+                mesh,
+                visibility,
+            });
+        });
+
+        version
+    }
+
+    /// Returns the meshes that finished since the last call, without waiting for the others.
+    pub fn finished(&self) -> impl Iterator<Item = MeshResult> + '_ {
+        self.receiver.try_iter()
+    }
+}
@@ -8,3 +8,13 @@ pub const FACE_BACK: FaceFlags = 16;
 pub const FACE_FRONT: FaceFlags = 32;
 pub const FACE_ALL: FaceFlags =
     FACE_LEFT | FACE_RIGHT | FACE_BOTTOM | FACE_TOP | FACE_BACK | FACE_FRONT;
+
+/// The flags of every face, in the same order as `BlockType::texture_indices`.
+pub const FACES: [FaceFlags; 6] = [
+    FACE_LEFT,
+    FACE_RIGHT,
+    FACE_BACK,
+    FACE_FRONT,
+    FACE_BOTTOM,
+    FACE_TOP,
+];
@@ -0,0 +1,110 @@
+use cgmath::{EuclideanSpace, Point3, Vector3};
+use std::time::Duration;
+
+use crate::{
+    aabb::Aabb,
+    geometry::Geometry,
+    vertex::BlockVertex,
+    world::{block::Block, chunk::CHUNK_ISIZE, light::Light, quad::Quad, World},
+};
+
+const GRAVITY: f32 = 20.0;
+const TERMINAL_VELOCITY: f32 = 40.0;
+
+/// A block with gravity (e.g. sand) that lost its support and is falling down.
+pub struct FallingBlock {
+    pub block: Block,
+    /// The position of the bottom corner of the block.
+    pub position: Point3<f32>,
+    /// The speed the block is falling at, in blocks per second.
+    pub velocity: f32,
+}
+
+impl FallingBlock {
+    pub fn new(block: Block, position: Point3<isize>) -> Self {
+        Self {
+            block,
+            position: position.cast().unwrap(),
+            velocity: 0.0,
+        }
+    }
+
+    /// Returns whether the block with gravity at `position` has nothing solid below it.
+    ///
+    /// Returns `None` if the chunk below it isn't loaded, in which case it shouldn't fall yet.
+    pub fn should_fall(world: &World, position: Point3<isize>) -> Option<bool> {
+        let below = position - Vector3::unit_y();
+        if !world
+            .chunks
+            .contains_key(&below.map(|x| x.div_euclid(CHUNK_ISIZE)))
+        {
+            return None;
+        }
+
+        Some(
+            !world
+                .get_block(below)
+                .is_some_and(|block| block.block_type.is_solid()),
+        )
+    }
+
+    /// Moves the block down, until it lands on the first solid block below it.
+    ///
+    /// Returns the position the block landed at, if it did.
+    pub fn update(&mut self, dt: Duration, world: &World) -> Option<Point3<isize>> {
+        self.velocity = (self.velocity + GRAVITY * dt.as_secs_f32()).min(TERMINAL_VELOCITY);
+        let new_y = self.position.y - self.velocity * dt.as_secs_f32();
+
+        let x = self.position.x as isize;
+        let z = self.position.z as isize;
+        let mut y = self.position.y.ceil() as isize - 1;
+        while y >= new_y.floor() as isize {
+            let position = Point3::new(x, y, z);
+            if !world
+                .chunks
+                .contains_key(&position.map(|x| x.div_euclid(CHUNK_ISIZE)))
+            {
+                // Wait above unloaded chunks until they are loaded
+                self.position.y = (y + 1) as f32;
+                self.velocity = 0.0;
+                return None;
+            }
+
+            if world
+                .get_block(position)
+                .is_some_and(|block| block.block_type.is_solid())
+            {
+                return Some(position + Vector3::unit_y());
+            }
+
+            y -= 1;
+        }
+
+        self.position.y = new_y;
+        None
+    }
+
+    /// Returns the box the falling block currently takes up.
+    pub fn aabb(&self) -> Aabb {
+        Aabb {
+            min: self.position,
+            max: self.position + Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Converts the falling block to `Geometry` to be rendered, lit by `light` on all sides.
+    pub fn to_geometry(&self, light: Light, start_index: u16) -> Geometry<BlockVertex, u16> {
+        let mut quad = Quad::new(Point3::origin(), 1, 1);
+        quad.block_type = Some(self.block.block_type);
+        quad.facing = self.block.facing;
+        quad.light = [light; 6];
+
+        let mut geometry = quad.to_geometry(start_index);
+        for vertex in &mut geometry.vertices {
+            vertex.position[0] += self.position.x;
+            vertex.position[1] += self.position.y;
+            vertex.position[2] += self.position.z;
+        }
+        geometry
+    }
+}
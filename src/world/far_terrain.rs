@@ -0,0 +1,381 @@
+use std::{
+    ops::Range,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use cgmath::{InnerSpace, Point2, Point3, Vector2, Vector3};
+use fxhash::{FxHashMap, FxHashSet};
+use wgpu::{RenderPass, RenderPipeline};
+
+use crate::{
+    aabb::Aabb,
+    geometry::Geometry,
+    geometry_buffers::GeometryBuffers,
+    render_context::RenderContext,
+    texture::Texture,
+    vertex::{FarTerrainVertex, Vertex},
+    view::View,
+    world::{
+        block::BlockType,
+        chunk::{terrain_height, CHUNK_ISIZE, SEA_LEVEL},
+        lod::{column_distance, LOD_DISTANCE},
+    },
+};
+
+/// How far from the camera the far terrain is drawn, in chunks.
+pub const FAR_TERRAIN_DISTANCE: isize = LOD_DISTANCE + 16;
+
+/// The number of columns along each axis of a region, which are meshed and drawn together.
+const REGION_SIZE: isize = 8;
+
+/// The distance between the samples of the heightmap, in blocks.
+const SAMPLE_SPACING: isize = 8;
+
+/// The number of cells of the heightmap along each axis of a column.
+const COLUMN_CELLS: isize = CHUNK_ISIZE / SAMPLE_SPACING;
+
+/// How far the edges of a tile hang down, to hide the gaps to the blocks and tiles next to it.
+const SKIRT_DEPTH: f32 = 16.0;
+
+const ROCK_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+/// A region of the far terrain, uploaded to the GPU.
+struct Region {
+    buffers: GeometryBuffers<u16>,
+    /// The indices of the tile of each column of the region, row by row.
+    column_indices: Vec<Range<u32>>,
+    aabb: Aabb,
+}
+
+/// A region meshed by a worker thread, waiting to be uploaded to the GPU.
+struct RegionMesh {
+    region: Point2<isize>,
+    geometry: Geometry<FarTerrainVertex, u16>,
+    column_indices: Vec<Range<u32>>,
+    aabb: Aabb,
+}
+
+/// The terrain beyond the loaded chunks, drawn as tiles of the generator's heightmap without
+/// generating any chunks.
+///
+/// Every column gets a tile, which is drawn until a chunk of the column is loaded or it's replaced
+/// by a level-of-detail column. The tiles are meshed in regions of `REGION_SIZE`² columns on
+/// worker threads, and the tiles of a region that are still drawn are drawn with as few calls as
+/// possible.
+pub struct FarTerrain {
+    render_pipeline: RenderPipeline,
+    regions: FxHashMap<Point2<isize>, Region>,
+    pending: FxHashSet<Point2<isize>>,
+    sender: Sender<RegionMesh>,
+    receiver: Receiver<RegionMesh>,
+}
+
+impl FarTerrain {
+    pub fn new(render_context: &RenderContext, view: &View) -> Self {
+        let texture_manager = render_context.texture_manager.as_ref().unwrap();
+        let pipeline_layout =
+            render_context
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("far_terrain_pipeline_layout"),
+                    push_constant_ranges: &[],
+                    bind_group_layouts: &[
+                        &texture_manager.bind_group_layout,
+                        &view.bind_group_layout,
+                    ],
+                });
+
+        let shader = render_context.device.create_shader_module(
+            &(wgpu::ShaderModuleDescriptor {
+                label: Some("far_terrain_shader"),
+                flags: wgpu::ShaderFlags::all(),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/far_terrain.wgsl").into(),
+                ),
+            }),
+        );
+
+        let render_pipeline =
+            render_context
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Far Terrain Render Pipeline"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "main",
+                        buffers: &[FarTerrainVertex::descriptor()],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "main",
+                        targets: &[wgpu::ColorTargetState {
+                            format: render_context.swap_chain_descriptor.format,
+                            blend: Some(wgpu::BlendState::REPLACE),
+                            write_mask: wgpu::ColorWrite::ALL,
+                        }],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        cull_mode: Some(wgpu::Face::Back),
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: Texture::DEPTH_FORMAT,
+                        depth_write_enabled: true,
+                        depth_compare: wgpu::CompareFunction::Greater,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                });
+
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            render_pipeline,
+            regions: FxHashMap::default(),
+            pending: FxHashSet::default(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Returns the combined size of the buffers of all regions, in bytes.
+    pub fn buffer_size(&self) -> wgpu::BufferAddress {
+        self.regions
+            .values()
+            .map(|region| region.buffers.size)
+            .sum()
+    }
+
+    /// Uploads the regions the worker threads finished, drops the regions that are too far away
+    /// and queues the closest regions that are missing.
+    pub fn update(&mut self, render_context: &RenderContext, camera_chunk: Point3<isize>) {
+        let camera_column = Point2::new(camera_chunk.x, camera_chunk.z);
+        let min = (camera_column - Vector2::new(1, 1) * FAR_TERRAIN_DISTANCE)
+            .map(|n| n.div_euclid(REGION_SIZE));
+        let max = (camera_column + Vector2::new(1, 1) * (FAR_TERRAIN_DISTANCE - 1))
+            .map(|n| n.div_euclid(REGION_SIZE));
+        let in_range = |region: Point2<isize>| {
+            (min.x..=max.x).contains(&region.x) && (min.y..=max.y).contains(&region.y)
+        };
+
+        for mesh in self.receiver.try_iter() {
+            self.pending.remove(&mesh.region);
+            if in_range(mesh.region) {
+                let buffers = GeometryBuffers::from_geometry(
+                    render_context,
+                    &mesh.geometry,
+                    wgpu::BufferUsage::empty(),
+                );
+                self.regions.insert(
+                    mesh.region,
+                    Region {
+                        buffers,
+                        column_indices: mesh.column_indices,
+                        aabb: mesh.aabb,
+                    },
+                );
+            }
+        }
+
+        self.regions.retain(|&region, _| in_range(region));
+
+        // Leave worker threads for meshing chunks, which are closer
+        let max_pending = (rayon::current_num_threads() / 2).max(1);
+        if self.pending.len() >= max_pending {
+            return;
+        }
+
+        let camera_region = camera_column.map(|n| n.div_euclid(REGION_SIZE));
+        let mut queue: Vec<_> = itertools::iproduct!(min.x..=max.x, min.y..=max.y)
+            .map(|(x, z)| Point2::new(x, z))
+            .filter(|region| !self.regions.contains_key(region) && !self.pending.contains(region))
+            .collect();
+        queue.sort_unstable_by_key(|&region| column_distance(region, camera_region));
+
+        let free = max_pending - self.pending.len();
+        for region in queue.into_iter().take(free) {
+            self.pending.insert(region);
+
+            let sender = self.sender.clone();
+            rayon::spawn(move || {
+                // The receiver is only gone when the world is, in which case the mesh isn't needed
+                let _ = sender.send(mesh_region(region));
+            });
+        }
+    }
+
+    /// Draws the tiles of the columns in the view, except for the ones `is_replaced` returns
+    /// `true` for.
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        view: &View,
+        is_replaced: impl Fn(Point2<isize>) -> bool,
+    ) -> usize {
+        render_pass.set_pipeline(&self.render_pipeline);
+
+        let camera_column = view
+            .camera
+            .position
+            .map(|x| (x.floor() as isize).div_euclid(CHUNK_ISIZE));
+        let camera_column = Point2::new(camera_column.x, camera_column.z);
+
+        let mut triangle_count = 0;
+        for (position, region) in &self.regions {
+            if !view.is_visible(&region.aabb) {
+                continue;
+            }
+            region.buffers.apply_buffers(render_pass);
+
+            // Tiles next to each other in the index buffer are drawn together
+            let mut draw = |indices: Range<u32>| {
+                render_pass.draw_indexed(indices.clone(), 0, 0..1);
+                triangle_count += indices.len() / 3;
+            };
+            let mut run: Option<Range<u32>> = None;
+            for (index, indices) in region.column_indices.iter().enumerate() {
+                let index = index as isize;
+                let column =
+                    position * REGION_SIZE + Vector2::new(index % REGION_SIZE, index / REGION_SIZE);
+                if column_distance(column, camera_column) >= FAR_TERRAIN_DISTANCE
+                    || is_replaced(column)
+                {
+                    if let Some(run) = run.take() {
+                        draw(run);
+                    }
+                } else {
+                    run = Some(run.map_or(indices.clone(), |run| run.start..indices.end));
+                }
+            }
+            if let Some(run) = run {
+                draw(run);
+            }
+        }
+        triangle_count
+    }
+}
+
+/// Shades a surface with the given normal like `shade` in `world.wgsl` shades the faces of
+/// blocks, blending between the faces by the squares of the normal.
+fn slope_shade(normal: Vector3<f32>) -> f32 {
+    normal.x.powi(2) * 0.6 + normal.z.powi(2) * 0.8 + normal.y.powi(2)
+}
+
+/// Returns the position of the vertex on the edge of a tile at `index`, going around the tile
+/// counter-clockwise when seen from above, starting at its corner with the lowest coordinates.
+fn perimeter_vertex(index: isize) -> (isize, isize) {
+    let n = COLUMN_CELLS;
+    match index / n {
+        0 => (index, 0),
+        1 => (n, index - n),
+        2 => (3 * n - index, n),
+        _ => (0, 4 * n - index),
+    }
+}
+
+/// Samples the heightmap of a region and meshes a tile for each of its columns.
+fn mesh_region(region: Point2<isize>) -> RegionMesh {
+    let fbm = noise::Fbm::new();
+
+    // The heights of the tops of the samples, with an extra ring of samples around the region
+    // to find the normals on its edges
+    let samples = REGION_SIZE * COLUMN_CELLS + 3;
+    let origin = region * (REGION_SIZE * CHUNK_ISIZE);
+    let heights: Vec<f32> = itertools::iproduct!(-1..samples - 1, -1..samples - 1)
+        .map(|(z, x)| {
+            let x = origin.x + x * SAMPLE_SPACING;
+            let z = origin.y + z * SAMPLE_SPACING;
+            (terrain_height(&fbm, x, z) + 1) as f32
+        })
+        .collect();
+    let height = |x: isize, z: isize| heights[((z + 1) * samples + x + 1) as usize];
+
+    let water_color = BlockType::Water.color().truncate();
+    let grass_color = BlockType::TallGrass.color().truncate();
+    let rock_color = Vector3::from(ROCK_COLOR);
+
+    let mut geometry = Geometry::default();
+    let mut column_indices = Vec::new();
+    let (mut min_y, mut max_y) = (f32::INFINITY, f32::NEG_INFINITY);
+    for (column_z, column_x) in itertools::iproduct!(0..REGION_SIZE, 0..REGION_SIZE) {
+        let start_index = geometry.indices.len() as u32;
+        let start_vertex = geometry.vertices.len() as u16;
+
+        let row = COLUMN_CELLS + 1;
+        for (z, x) in itertools::iproduct!(0..row, 0..row) {
+            let (sample_x, sample_z) = (column_x * COLUMN_CELLS + x, column_z * COLUMN_CELLS + z);
+            let y = height(sample_x, sample_z);
+            let (y, color) = if y < SEA_LEVEL as f32 {
+                (SEA_LEVEL as f32, water_color)
+            } else {
+                let normal = Vector3::new(
+                    height(sample_x - 1, sample_z) - height(sample_x + 1, sample_z),
+                    2.0 * SAMPLE_SPACING as f32,
+                    height(sample_x, sample_z - 1) - height(sample_x, sample_z + 1),
+                )
+                .normalize();
+                let steepness = ((1.0 - normal.y) * 4.0).min(1.0);
+                let color = grass_color + (rock_color - grass_color) * steepness;
+                (y, color * slope_shade(normal))
+            };
+
+            min_y = min_y.min(y - SKIRT_DEPTH);
+            max_y = max_y.max(y);
+            geometry.vertices.push(FarTerrainVertex {
+                position: [
+                    (origin.x + sample_x * SAMPLE_SPACING) as f32,
+                    y,
+                    (origin.y + sample_z * SAMPLE_SPACING) as f32,
+                ],
+                color: color.into(),
+            });
+        }
+
+        let grid_index = |x: isize, z: isize| start_vertex + (z * row + x) as u16;
+        for (z, x) in itertools::iproduct!(0..COLUMN_CELLS, 0..COLUMN_CELLS) {
+            let (v00, v01) = (grid_index(x, z), grid_index(x, z + 1));
+            let (v10, v11) = (grid_index(x + 1, z), grid_index(x + 1, z + 1));
+            geometry
+                .indices
+                .extend_from_slice(&[v00, v01, v11, v00, v11, v10]);
+        }
+
+        // The skirt hangs down from the edges of the tile, facing outwards
+        let perimeter = 4 * COLUMN_CELLS;
+        let skirt_vertex = geometry.vertices.len() as u16;
+        for index in 0..perimeter {
+            let (x, z) = perimeter_vertex(index);
+            let top = geometry.vertices[grid_index(x, z) as usize];
+            let [x, y, z] = top.position;
+            geometry.vertices.push(FarTerrainVertex {
+                position: [x, y - SKIRT_DEPTH, z],
+                color: (Vector3::from(top.color) * 0.7).into(),
+            });
+        }
+        for index in 0..perimeter {
+            let next = (index + 1) % perimeter;
+            let (a, b) = (perimeter_vertex(index), perimeter_vertex(next));
+            let (top_a, top_b) = (grid_index(a.0, a.1), grid_index(b.0, b.1));
+            let (bottom_a, bottom_b) = (skirt_vertex + index as u16, skirt_vertex + next as u16);
+            geometry
+                .indices
+                .extend_from_slice(&[top_a, top_b, bottom_a, top_b, bottom_b, bottom_a]);
+        }
+
+        column_indices.push(start_index..geometry.indices.len() as u32);
+    }
+
+    let size = (REGION_SIZE * CHUNK_ISIZE) as f32;
+    let aabb = Aabb {
+        min: Point3::new(origin.x as f32, min_y, origin.y as f32),
+        max: Point3::new(origin.x as f32 + size, max_y, origin.y as f32 + size),
+    };
+
+    RegionMesh {
+        region,
+        geometry,
+        column_indices,
+        aabb,
+    }
+}
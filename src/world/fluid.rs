@@ -0,0 +1,212 @@
+use cgmath::{Point3, Vector3};
+
+use crate::world::{
+    block::{Block, BlockType},
+    chunk::CHUNK_ISIZE,
+    World, WORLD_HEIGHT,
+};
+
+/// Level of a water source block.
+pub const SOURCE_LEVEL: u8 = 0;
+/// Highest level of flowing water, i.e. how far water flows horizontally from a source.
+pub const MAX_FLOW_LEVEL: u8 = 7;
+/// Level of water that is falling down, and spreads like a source when it lands.
+pub const FALLING_LEVEL: u8 = 8;
+
+const HORIZONTAL: [Vector3<isize>; 4] = [
+    Vector3::new(-1, 0, 0),
+    Vector3::new(1, 0, 0),
+    Vector3::new(0, 0, -1),
+    Vector3::new(0, 0, 1),
+];
+
+fn water(level: u8) -> Block {
+    Block {
+        level,
+        ..Block::new(BlockType::Water)
+    }
+}
+
+fn is_water(block: Option<&Block>) -> bool {
+    block.map(|block| block.block_type) == Some(BlockType::Water)
+}
+
+/// Returns whether water can flow into a block, i.e. it is air or washed away by water.
+fn can_flow_into(block: Option<&Block>) -> bool {
+    match block {
+        Some(block) => block.block_type != BlockType::Water && !block.block_type.is_solid(),
+        None => true,
+    }
+}
+
+/// Returns whether the position is within the height of the world, outside of which there are
+/// never any chunks.
+fn in_world(position: Point3<isize>) -> bool {
+    (0..WORLD_HEIGHT * CHUNK_ISIZE).contains(&position.y)
+}
+
+/// Returns the height of the surface of water with the given level, relative to the bottom of
+/// its block.
+pub fn surface_height(level: u8, water_above: bool) -> f32 {
+    if water_above || level == SOURCE_LEVEL || level == FALLING_LEVEL {
+        1.0
+    } else {
+        (FALLING_LEVEL - level) as f32 / FALLING_LEVEL as f32
+    }
+}
+
+/// Returns the water blocks that have to start flowing now that the chunk at `chunk_position`
+/// is loaded, i.e. the water in or next to the chunk that can flow into a block of it or of
+/// another loaded chunk.
+///
+/// Water next to chunks that aren't loaded yet is left alone, and found once they are. This
+/// includes the water around the chunk that `update` was waiting for it to load.
+pub fn chunk_loaded(world: &World, chunk_position: Point3<isize>) -> Vec<Point3<isize>> {
+    let chunk = &world.chunks[&chunk_position];
+    let origin = chunk_position * CHUNK_ISIZE;
+    let in_chunk =
+        |position: Point3<isize>| position.map(|x| x.div_euclid(CHUNK_ISIZE)) == chunk_position;
+    // The block at the position, or `None` if its chunk isn't loaded
+    let block_at = |position: Point3<isize>| {
+        if in_chunk(position) {
+            let b = position.map(|x| x.rem_euclid(CHUNK_ISIZE) as usize);
+            Some(chunk.blocks[b.y][b.z][b.x].as_ref())
+        } else {
+            world
+                .chunks
+                .get(&position.map(|x| x.div_euclid(CHUNK_ISIZE)))
+                .map(|_| world.get_block(position))
+        }
+    };
+
+    let mut flowing = Vec::new();
+    let range = -1..CHUNK_ISIZE + 1;
+    for (x, y, z) in itertools::iproduct!(range.clone(), range.clone(), range) {
+        // Besides the chunk itself, only the water in the layers touching its faces can flow
+        // into it
+        let outside = [x, y, z]
+            .iter()
+            .filter(|&&n| !(0..CHUNK_ISIZE).contains(&n))
+            .count();
+        if outside > 1 {
+            continue;
+        }
+
+        let position = origin + Vector3::new(x, y, z);
+        if !block_at(position).is_some_and(is_water) {
+            continue;
+        }
+
+        let can_flow = HORIZONTAL
+            .iter()
+            .chain(&[-Vector3::unit_y()])
+            .map(|offset| position + offset)
+            .any(|neighbour| block_at(neighbour).is_some_and(can_flow_into));
+        if can_flow {
+            flowing.push(position);
+        }
+    }
+
+    flowing
+}
+
+/// Simulates one step of the water at `position`.
+///
+/// Returns the blocks that have to be changed, or `None` if the water is next to a chunk that
+/// isn't loaded. `chunk_loaded` finds the water again once it is.
+pub fn update(
+    world: &World,
+    position: Point3<isize>,
+) -> Option<Vec<(Point3<isize>, Option<Block>)>> {
+    let neighbour_chunks_loaded = [
+        Vector3::new(0, 0, 0),
+        Vector3::new(0, 1, 0),
+        Vector3::new(0, -1, 0),
+    ]
+    .iter()
+    .chain(HORIZONTAL.iter())
+    .all(|offset| {
+        let neighbour = position + offset;
+        !in_world(neighbour)
+            || world
+                .chunks
+                .contains_key(&neighbour.map(|x| x.div_euclid(CHUNK_ISIZE)))
+    });
+    if !neighbour_chunks_loaded {
+        return None;
+    }
+
+    let block = match world.get_block(position) {
+        Some(block) if block.block_type == BlockType::Water => *block,
+        _ => return Some(Vec::new()),
+    };
+
+    let above = world.get_block(position + Vector3::unit_y());
+    let below = world.get_block(position - Vector3::unit_y());
+    // Water can't flow out of the bottom of the world, so it spreads there like on solid ground
+    let on_bottom = !in_world(position - Vector3::unit_y());
+    let horizontal: Vec<_> = HORIZONTAL
+        .iter()
+        .map(|offset| (position + offset, world.get_block(position + offset)))
+        .collect();
+
+    // Flowing water takes its level from the water it's flowing from
+    let level = if block.level == SOURCE_LEVEL {
+        SOURCE_LEVEL
+    } else if is_water(above) {
+        FALLING_LEVEL
+    } else {
+        let sources = horizontal
+            .iter()
+            .filter(|(_, block)| is_water(*block) && block.unwrap().level == SOURCE_LEVEL)
+            .count();
+        let supported = on_bottom
+            || below.is_some_and(|below| {
+                below.block_type.is_solid()
+                    || (is_water(Some(below)) && below.level == SOURCE_LEVEL)
+            });
+
+        if sources >= 2 && supported {
+            // Water flowing between two sources becomes a source itself
+            SOURCE_LEVEL
+        } else {
+            horizontal
+                .iter()
+                .filter(|(_, block)| is_water(*block))
+                .map(|(_, block)| match block.unwrap().level {
+                    FALLING_LEVEL => 1,
+                    level => level + 1,
+                })
+                .min()
+                .unwrap_or(u8::MAX)
+        }
+    };
+
+    if level > MAX_FLOW_LEVEL && level != FALLING_LEVEL {
+        // Dried up
+        return Some(vec![(position, None)]);
+    } else if level != block.level {
+        // The neighbours will be updated when the block is set, after which it spreads
+        return Some(vec![(position, Some(Block { level, ..block }))]);
+    }
+
+    let mut changes = Vec::new();
+    if !on_bottom && can_flow_into(below) {
+        changes.push((position - Vector3::unit_y(), Some(water(FALLING_LEVEL))));
+    } else if !is_water(below) {
+        let next_level = match level {
+            FALLING_LEVEL => 1,
+            level => level + 1,
+        };
+
+        if next_level <= MAX_FLOW_LEVEL {
+            for &(neighbour, block) in &horizontal {
+                if can_flow_into(block) {
+                    changes.push((neighbour, Some(water(next_level))));
+                }
+            }
+        }
+    }
+
+    Some(changes)
+}
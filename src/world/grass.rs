@@ -0,0 +1,47 @@
+use cgmath::{Point3, Vector3};
+use rand::Rng;
+
+use crate::world::{
+    block::{Block, BlockType},
+    World,
+};
+
+/// The number of random blocks around a grass block it tries to spread to per random tick.
+const SPREAD_ATTEMPTS: usize = 4;
+
+/// Returns whether the block at `position` receives light, i.e. there's no opaque block on top
+/// of it.
+fn is_lit(world: &World, position: Point3<isize>) -> bool {
+    !world
+        .get_block(position + Vector3::unit_y())
+        .is_some_and(|block| block.block_type.is_opaque())
+}
+
+/// Randomly ticks the grass block at `position`.
+///
+/// Grass covered by an opaque block dies and turns into dirt. Otherwise, it spreads to lit dirt
+/// blocks up to one block away horizontally, one block above and three blocks below it.
+pub fn random_tick(world: &World, position: Point3<isize>) -> Vec<(Point3<isize>, Option<Block>)> {
+    if !is_lit(world, position) {
+        return vec![(position, Some(Block::new(BlockType::Dirt)))];
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut changes = Vec::new();
+    for _ in 0..SPREAD_ATTEMPTS {
+        let target = position
+            + Vector3::new(
+                rng.gen_range(-1, 2),
+                rng.gen_range(-3, 2),
+                rng.gen_range(-1, 2),
+            );
+
+        let is_dirt =
+            world.get_block(target).map(|block| block.block_type) == Some(BlockType::Dirt);
+        if is_dirt && is_lit(world, target) {
+            changes.push((target, Some(Block::new(BlockType::Grass))));
+        }
+    }
+
+    changes
+}
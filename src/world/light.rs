@@ -0,0 +1,385 @@
+use std::collections::VecDeque;
+
+use cgmath::{Point3, Vector3};
+use fxhash::FxHashSet;
+
+use crate::world::{
+    chunk::{CHUNK_ISIZE, CHUNK_SIZE},
+    World,
+};
+
+/// The highest light level, e.g. of direct sunlight.
+pub const MAX_LIGHT: u8 = 15;
+
+/// The sky light and block light level of a block, packed into a single byte.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Light(u8);
+
+/// One of the two kinds of light stored in `Light`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light coming from the sky, which travels down without getting darker.
+    Sky,
+    /// Light emitted by blocks, e.g. torches.
+    Block,
+}
+
+impl Light {
+    pub const fn new(sky: u8, block: u8) -> Self {
+        Self(sky << 4 | block)
+    }
+
+    pub const fn sky(self) -> u8 {
+        self.0 >> 4
+    }
+
+    pub const fn block(self) -> u8 {
+        self.0 & 0xf
+    }
+
+    pub const fn get(self, channel: LightChannel) -> u8 {
+        match channel {
+            LightChannel::Sky => self.sky(),
+            LightChannel::Block => self.block(),
+        }
+    }
+
+    pub fn set(&mut self, channel: LightChannel, level: u8) {
+        *self = match channel {
+            LightChannel::Sky => Self::new(level, self.block()),
+            LightChannel::Block => Self::new(self.sky(), level),
+        };
+    }
+
+    /// Returns the light as a vertex attribute, with both levels mapped to 0..1.
+    pub fn to_vertex(self) -> [f32; 2] {
+        [
+            self.sky() as f32 / MAX_LIGHT as f32,
+            self.block() as f32 / MAX_LIGHT as f32,
+        ]
+    }
+
+    /// Returns the brightest level of both lights for each channel.
+    pub fn max(self, other: Self) -> Self {
+        Self::new(self.sky().max(other.sky()), self.block().max(other.block()))
+    }
+}
+
+/// The light of the layers of blocks bordering a chunk on each side, in the same order as
+/// `BlockType::texture_indices`: left, right, back, front, bottom, top.
+///
+/// The layers are indexed like `Chunk::blocks`, leaving out the axis they're on, i.e. `[y][z]`,
+/// `[y][x]` and `[z][x]` respectively.
+pub type BorderLight = [[[Light; CHUNK_SIZE]; CHUNK_SIZE]; 6];
+
+/// The offsets of the neighbours of a block, in the same order as `BorderLight`.
+pub const NEIGHBOURS: [Vector3<isize>; 6] = [
+    Vector3::new(-1, 0, 0),
+    Vector3::new(1, 0, 0),
+    Vector3::new(0, 0, -1),
+    Vector3::new(0, 0, 1),
+    Vector3::new(0, -1, 0),
+    Vector3::new(0, 1, 0),
+];
+
+/// Returns the light at `position`, or `None` if its chunk isn't loaded.
+pub fn get(world: &World, position: Point3<isize>) -> Option<Light> {
+    let chunk = world
+        .chunks
+        .get(&position.map(|x| x.div_euclid(CHUNK_ISIZE)))?;
+    let b = position.map(|x| x.rem_euclid(CHUNK_ISIZE) as usize);
+    Some(chunk.light[b.y][b.z][b.x])
+}
+
+/// Returns the light of the blocks next to each face of the block at `position`, in the same
+/// order as `NEIGHBOURS`. Faces next to unloaded chunks are fully lit.
+pub fn face_light(world: &World, position: Point3<isize>) -> [Light; 6] {
+    let mut light = [Light::default(); 6];
+    for (light, offset) in light.iter_mut().zip(&NEIGHBOURS) {
+        *light = get(world, position + offset).unwrap_or(Light::new(MAX_LIGHT, 0));
+    }
+    light
+}
+
+/// Returns the light of the blocks bordering the chunk at `chunk_position`.
+pub fn border_light(world: &World, chunk_position: Point3<isize>) -> Box<BorderLight> {
+    let mut border = Box::new([[[Light::new(MAX_LIGHT, 0); CHUNK_SIZE]; CHUNK_SIZE]; 6]);
+    for (side, offset) in NEIGHBOURS.iter().enumerate() {
+        let chunk = match world.chunks.get(&(chunk_position + offset)) {
+            Some(chunk) => chunk,
+            None => continue,
+        };
+
+        // The layer of the neighbouring chunk that touches this chunk
+        let layer = |offset: isize| if offset < 0 { CHUNK_SIZE - 1 } else { 0 };
+        for a in 0..CHUNK_SIZE {
+            for b in 0..CHUNK_SIZE {
+                border[side][a][b] = if offset.x != 0 {
+                    chunk.light[a][b][layer(offset.x)]
+                } else if offset.z != 0 {
+                    chunk.light[a][layer(offset.z)][b]
+                } else {
+                    chunk.light[layer(offset.y)][a][b]
+                };
+            }
+        }
+    }
+    border
+}
+
+fn is_opaque(world: &World, position: Point3<isize>) -> bool {
+    world
+        .get_block(position)
+        .is_some_and(|block| block.block_type.is_opaque())
+}
+
+fn emission(world: &World, position: Point3<isize>) -> u8 {
+    world
+        .get_block(position)
+        .map_or(0, |block| block.block_type.light_emission())
+}
+
+/// Sets the light level of `channel` at `position`, and marks the chunks that have to be
+/// re-meshed because of it as dirty.
+fn set(
+    world: &mut World,
+    position: Point3<isize>,
+    channel: LightChannel,
+    level: u8,
+    dirty: &mut FxHashSet<Point3<isize>>,
+) {
+    let chunk_position = position.map(|x| x.div_euclid(CHUNK_ISIZE));
+    let b = position.map(|x| x.rem_euclid(CHUNK_ISIZE) as usize);
+    if let Some(chunk) = world.chunks.get_mut(&chunk_position) {
+        chunk.light[b.y][b.z][b.x].set(channel, level);
+    }
+
+    // The faces of blocks in neighbouring chunks might be lit by this block
+    dirty.insert(chunk_position);
+    for offset in &NEIGHBOURS {
+        let neighbour_chunk = (position + offset).map(|x| x.div_euclid(CHUNK_ISIZE));
+        if neighbour_chunk != chunk_position {
+            dirty.insert(neighbour_chunk);
+        }
+    }
+}
+
+/// Returns the level `channel` has after travelling from a block with `level` towards `offset`.
+fn spread_level(channel: LightChannel, level: u8, offset: Vector3<isize>) -> u8 {
+    if channel == LightChannel::Sky && level == MAX_LIGHT && offset.y == -1 {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Spreads the light of `channel` from every block in `queue` to its neighbours.
+fn propagate(
+    world: &mut World,
+    channel: LightChannel,
+    mut queue: VecDeque<Point3<isize>>,
+    dirty: &mut FxHashSet<Point3<isize>>,
+) {
+    while let Some(position) = queue.pop_front() {
+        let level = match get(world, position) {
+            Some(light) => light.get(channel),
+            None => continue,
+        };
+
+        for &offset in &NEIGHBOURS {
+            let neighbour = position + offset;
+            let new_level = spread_level(channel, level, offset);
+            if new_level == 0 || is_opaque(world, neighbour) {
+                continue;
+            }
+
+            match get(world, neighbour) {
+                Some(light) if light.get(channel) < new_level => {
+                    set(world, neighbour, channel, new_level, dirty);
+                    queue.push_back(neighbour);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Removes the light of `channel` that spread from the blocks in `queue`, which had the given
+/// levels before they were darkened.
+///
+/// Blocks that are lit by other sources are added to `sources`, so their light can spread into the
+/// darkened area again.
+fn remove(
+    world: &mut World,
+    channel: LightChannel,
+    mut queue: VecDeque<(Point3<isize>, u8)>,
+    sources: &mut VecDeque<Point3<isize>>,
+    dirty: &mut FxHashSet<Point3<isize>>,
+) {
+    while let Some((position, level)) = queue.pop_front() {
+        for &offset in &NEIGHBOURS {
+            let neighbour = position + offset;
+            let neighbour_level = match get(world, neighbour) {
+                Some(light) => light.get(channel),
+                None => continue,
+            };
+            if neighbour_level == 0 {
+                continue;
+            }
+
+            if neighbour_level <= spread_level(channel, level, offset) {
+                // This light came from the removed light
+                set(world, neighbour, channel, 0, dirty);
+                queue.push_back((neighbour, neighbour_level));
+
+                if channel == LightChannel::Block {
+                    let emission = emission(world, neighbour);
+                    if emission > 0 {
+                        set(world, neighbour, channel, emission, dirty);
+                        sources.push_back(neighbour);
+                    }
+                }
+            } else {
+                sources.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Returns whether the block at `position` is lit by the sky directly, because it's at the top of
+/// the world or of a chunk with no chunk loaded above it.
+///
+/// The sky is assumed to be open above chunks that aren't loaded yet, so surface chunks are lit
+/// right away. `chunk_loaded` darkens the blocks below a chunk again where it blocks the sky.
+fn is_below_sky(world: &World, position: Point3<isize>) -> bool {
+    !is_opaque(world, position) && get(world, position + Vector3::unit_y()).is_none()
+}
+
+/// Updates the light around `position` after the block there changed from one with the given
+/// opacity and light emission.
+///
+/// Returns the chunks that have to be re-meshed.
+pub fn block_changed(
+    world: &mut World,
+    position: Point3<isize>,
+    was_opaque: bool,
+    old_emission: u8,
+) -> FxHashSet<Point3<isize>> {
+    let mut dirty = FxHashSet::default();
+    let is_opaque = is_opaque(world, position);
+    let emission = emission(world, position);
+    if is_opaque == was_opaque && emission == old_emission {
+        return dirty;
+    }
+
+    for &channel in &[LightChannel::Sky, LightChannel::Block] {
+        let level = get(world, position).map_or(0, |light| light.get(channel));
+        let mut sources = VecDeque::new();
+
+        set(world, position, channel, 0, &mut dirty);
+        remove(
+            world,
+            channel,
+            VecDeque::from(vec![(position, level)]),
+            &mut sources,
+            &mut dirty,
+        );
+
+        if channel == LightChannel::Block && emission > 0 {
+            set(world, position, channel, emission, &mut dirty);
+            sources.push_back(position);
+        }
+        if channel == LightChannel::Sky && is_below_sky(world, position) {
+            set(world, position, channel, MAX_LIGHT, &mut dirty);
+            sources.push_back(position);
+        }
+        if !is_opaque {
+            // Let the light of the neighbours flow into the block
+            sources.extend(NEIGHBOURS.iter().map(|offset| position + offset));
+        }
+
+        propagate(world, channel, sources, &mut dirty);
+    }
+
+    dirty
+}
+
+/// Calculates the light of the newly loaded chunk at `chunk_position`, and spreads the light of
+/// its neighbours into it and its light into its neighbours. The sky light of the chunk below that
+/// the chunk blocks is removed.
+///
+/// Returns the chunks that have to be re-meshed.
+pub fn chunk_loaded(world: &mut World, chunk_position: Point3<isize>) -> FxHashSet<Point3<isize>> {
+    let mut dirty = FxHashSet::default();
+    let mut sky_sources = VecDeque::new();
+    let mut block_sources = VecDeque::new();
+
+    let offset = chunk_position * CHUNK_ISIZE;
+    for (x, y, z) in itertools::iproduct!(0..CHUNK_ISIZE, 0..CHUNK_ISIZE, 0..CHUNK_ISIZE) {
+        let position = offset + Vector3::new(x, y, z);
+
+        let emission = emission(world, position);
+        if emission > 0 {
+            set(world, position, LightChannel::Block, emission, &mut dirty);
+            block_sources.push_back(position);
+        }
+        if is_below_sky(world, position) {
+            set(world, position, LightChannel::Sky, MAX_LIGHT, &mut dirty);
+            sky_sources.push_back(position);
+        }
+    }
+
+    // Blocks in neighbouring chunks touching this chunk
+    for (side, neighbour_offset) in NEIGHBOURS.iter().enumerate() {
+        if !world
+            .chunks
+            .contains_key(&(chunk_position + neighbour_offset))
+        {
+            continue;
+        }
+
+        for (a, b) in itertools::iproduct!(0..CHUNK_ISIZE, 0..CHUNK_ISIZE) {
+            let local = match side {
+                0 => Vector3::new(-1, a, b),
+                1 => Vector3::new(CHUNK_ISIZE, a, b),
+                2 => Vector3::new(b, a, -1),
+                3 => Vector3::new(b, a, CHUNK_ISIZE),
+                4 => Vector3::new(b, -1, a),
+                _ => Vector3::new(b, CHUNK_ISIZE, a),
+            };
+            let position = offset + local;
+
+            let light = get(world, position).unwrap_or_default();
+            if light.sky() > 0 {
+                sky_sources.push_back(position);
+            }
+            if light.block() > 0 {
+                block_sources.push_back(position);
+            }
+        }
+    }
+
+    propagate(world, LightChannel::Sky, sky_sources, &mut dirty);
+    propagate(world, LightChannel::Block, block_sources, &mut dirty);
+
+    // The top of the chunk below was lit as if the sky was open, which it only is where the sky
+    // light passes through this chunk
+    let mut darkened = VecDeque::new();
+    for (x, z) in itertools::iproduct!(0..CHUNK_ISIZE, 0..CHUNK_ISIZE) {
+        let position = offset + Vector3::new(x, 0, z);
+        let below = position - Vector3::unit_y();
+        let is_sky_lit =
+            |position| get(world, position).is_some_and(|light| light.sky() == MAX_LIGHT);
+        if is_sky_lit(below) && !is_sky_lit(position) {
+            set(world, below, LightChannel::Sky, 0, &mut dirty);
+            darkened.push_back((below, MAX_LIGHT));
+        }
+    }
+    if !darkened.is_empty() {
+        let mut sources = VecDeque::new();
+        remove(world, LightChannel::Sky, darkened, &mut sources, &mut dirty);
+        propagate(world, LightChannel::Sky, sources, &mut dirty);
+    }
+
+    dirty
+}
@@ -0,0 +1,384 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use cgmath::{EuclideanSpace, Point2, Point3, Vector2};
+use fxhash::FxHashMap;
+
+use crate::{
+    render_context::RenderContext,
+    settings::Settings,
+    vertex::ChunkVertex,
+    view::View,
+    world::{
+        block::{Block, BlockType, RenderClass},
+        block_shape::BlockShape,
+        chunk::{
+            isolated_borders, BorderBlocks, BorderOccluders, Chunk, ChunkMesh, CHUNK_ISIZE,
+            CHUNK_SIZE,
+        },
+        chunk_arena::{ChunkArena, ChunkBuffers},
+        World, RENDER_DISTANCE, WORLD_HEIGHT,
+    },
+};
+
+/// How far from the camera columns of chunks are drawn with a lower level of detail, in chunks.
+pub const LOD_DISTANCE: isize = 32;
+
+/// Returns the distance of a column from the column the camera is in, in chunks. The columns that
+/// are loaded as chunks are the ones closer than `RENDER_DISTANCE`.
+pub fn column_distance(column: Point2<isize>, camera_column: Point2<isize>) -> isize {
+    let offset = column - camera_column;
+    offset.x.max(-offset.x - 1).max(offset.y.max(-offset.y - 1))
+}
+
+/// Returns how many blocks along each axis are merged into a single cell in the level of detail
+/// of a column at the given distance, or `None` if the column isn't drawn with a lower level of
+/// detail.
+fn lod_scale(distance: isize) -> Option<usize> {
+    match distance {
+        d if d < RENDER_DISTANCE => None,
+        d if d < 16 => Some(2),
+        d if d < 24 => Some(4),
+        d if d < LOD_DISTANCE => Some(8),
+        _ => None,
+    }
+}
+
+/// The chunks of a column of the world, meshed with a lower level of detail.
+pub struct LodColumn {
+    /// How many blocks along each axis make up a cell of the mesh.
+    pub scale: usize,
+    /// The buffers of the chunks of the column that have any geometry.
+    pub chunks: Vec<(Point3<isize>, ChunkBuffers)>,
+}
+
+/// A column meshed by a worker thread, waiting to be uploaded to the GPU.
+struct LodResult {
+    column: Point2<isize>,
+    scale: usize,
+    meshes: Vec<(Point3<isize>, ChunkMesh<ChunkVertex>)>,
+}
+
+/// The columns of chunks beyond the render distance, which are drawn with a lower level of detail
+/// the further away they are.
+///
+/// The columns are generated (or loaded) and meshed on worker threads, and only their meshes are
+/// kept, so they take a lot less memory than loaded chunks.
+pub struct Lod {
+    pub columns: FxHashMap<Point2<isize>, LodColumn>,
+    /// The columns being meshed, with the scale they're meshed with.
+    pending: FxHashMap<Point2<isize>, usize>,
+    sender: Sender<LodResult>,
+    receiver: Receiver<LodResult>,
+}
+
+impl Default for Lod {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            columns: FxHashMap::default(),
+            pending: FxHashMap::default(),
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl Lod {
+    /// Returns the combined size of the buffers of all columns, in bytes.
+    pub fn buffer_size(&self) -> wgpu::BufferAddress {
+        self.columns
+            .values()
+            .flat_map(|column| column.chunks.iter())
+            .map(|(_, buffers)| buffers.size())
+            .sum()
+    }
+
+    /// Adds draws of the geometry of the given `RenderClass` of every column in the view to the
+    /// `ChunkArena`, except for the chunks that are loaded and meshed at full detail.
+    ///
+    /// Returns the number of triangles that will be drawn.
+    pub fn push_draws(
+        &self,
+        chunk_arena: &mut ChunkArena,
+        view: &View,
+        chunks: &FxHashMap<Point3<isize>, Chunk>,
+        render_class: RenderClass,
+    ) -> usize {
+        let mut triangle_count = 0;
+        for column in self.columns.values() {
+            for (position, buffers) in &column.chunks {
+                let meshed = chunks
+                    .get(position)
+                    .is_some_and(|chunk| chunk.buffers.is_some());
+                if !meshed && Chunk::is_visible(position * CHUNK_ISIZE, view) {
+                    triangle_count += chunk_arena.push(buffers, *position, render_class);
+                }
+            }
+        }
+        triangle_count
+    }
+}
+
+/// Uploads the columns the worker threads finished, drops the columns that are too far away or
+/// fully replaced by loaded chunks, and queues the closest columns that are missing or have the
+/// wrong level of detail.
+pub fn update(world: &mut World, render_context: &RenderContext, camera_chunk: Point3<isize>) {
+    for result in world.lod.receiver.try_iter() {
+        if world.lod.pending.get(&result.column) != Some(&result.scale) {
+            // Queued again with a different scale since
+            continue;
+        }
+        world.lod.pending.remove(&result.column);
+
+        let chunk_arena = &mut world.chunk_arena;
+        let chunks: Vec<_> = result
+            .meshes
+            .iter()
+            .map(|(position, mesh)| (*position, chunk_arena.upload(render_context, mesh)))
+            .collect();
+        world.chunk_uploaded_bytes += chunks
+            .iter()
+            .map(|(_, buffers)| buffers.size())
+            .sum::<u64>();
+        world.lod.columns.insert(
+            result.column,
+            LodColumn {
+                scale: result.scale,
+                chunks,
+            },
+        );
+    }
+
+    let camera_column = Point2::new(camera_chunk.x, camera_chunk.z);
+    let chunks = &world.chunks;
+    world.lod.columns.retain(|&column, _| {
+        let distance = column_distance(column, camera_column);
+        let replaced = distance < RENDER_DISTANCE
+            && (0..WORLD_HEIGHT).all(|y| {
+                chunks
+                    .get(&Point3::new(column.x, y, column.y))
+                    .is_some_and(|chunk| chunk.buffers.is_some())
+            });
+        distance < LOD_DISTANCE && !replaced
+    });
+
+    // Leave worker threads for meshing loaded chunks, which are closer
+    let max_pending = (rayon::current_num_threads() / 2).max(1);
+    if world.lod.pending.len() >= max_pending {
+        return;
+    }
+
+    let mut queue = Vec::new();
+    for (x, z) in itertools::iproduct!(-LOD_DISTANCE..LOD_DISTANCE, -LOD_DISTANCE..LOD_DISTANCE) {
+        let column = camera_column + Vector2::new(x, z);
+        let distance = column_distance(column, camera_column);
+        if let Some(scale) = lod_scale(distance) {
+            let current = world.lod.columns.get(&column).map(|column| column.scale);
+            if current != Some(scale) && world.lod.pending.get(&column) != Some(&scale) {
+                queue.push((distance, column, scale));
+            }
+        }
+    }
+    queue.sort_unstable_by_key(|&(distance, _, _)| distance);
+
+    let free = max_pending - world.lod.pending.len();
+    for (_, column, scale) in queue.into_iter().take(free) {
+        world.lod.pending.insert(column, scale);
+
+        let sender = world.lod.sender.clone();
+        let store = world.chunk_database.clone();
+        let settings = render_context.settings;
+        rayon::spawn(move || {
+            let meshes = mesh_column(column, scale, &store, &settings);
+            // The receiver is only gone when the world is, in which case the mesh isn't needed
+            let _ = sender.send(LodResult {
+                column,
+                scale,
+                meshes,
+            });
+        });
+    }
+}
+
+/// The blocks of a column of the world, downsampled into cells of `scale`³ blocks.
+struct LodCells {
+    /// The number of cells along the X and Z axes.
+    size: usize,
+    /// The number of cells along the Y axis.
+    height: usize,
+    cells: Vec<Option<Block>>,
+}
+
+impl LodCells {
+    /// Downsamples the chunks of a column, from the bottom to the top.
+    ///
+    /// A cell is filled if at least half of its blocks are cubes, with the highest of them, so
+    /// that e.g. grass stays on top of hills. Other shapes are too small to be seen from afar.
+    fn new(chunks: &[Box<Chunk>], scale: usize) -> Self {
+        let size = CHUNK_SIZE / scale;
+        let height = size * chunks.len();
+        let mut cells = vec![None; size * height * size];
+
+        for (chunk_y, chunk) in chunks.iter().enumerate() {
+            for (x, y, z) in itertools::iproduct!(0..size, 0..size, 0..size) {
+                let mut count = 0;
+                let mut highest = None;
+                for (by, bz, bx) in itertools::iproduct!(
+                    (y * scale..(y + 1) * scale).rev(),
+                    z * scale..(z + 1) * scale,
+                    x * scale..(x + 1) * scale
+                ) {
+                    if let Some(block) = chunk.blocks[by][bz][bx] {
+                        if block.block_type.shape() == BlockShape::Cube {
+                            count += 1;
+                            highest = highest.or(Some(block));
+                        }
+                    }
+                }
+
+                if count * 2 >= scale.pow(3) {
+                    let y = chunk_y * size + y;
+                    cells[(y * size + z) * size + x] = highest;
+                }
+            }
+        }
+
+        Self {
+            size,
+            height,
+            cells,
+        }
+    }
+
+    /// Returns the cell at the given position, which may be outside of the column.
+    ///
+    /// Below the column is solid ground and above it is air. Next to the column, the cells on its
+    /// edges are repeated, except for the ones on the surface: these get an air cell next to them
+    /// instead, so their sides are drawn as a skirt that hides the seams to neighbouring columns
+    /// with a different level of detail.
+    fn get(&self, x: isize, y: isize, z: isize) -> Option<Block> {
+        if y < 0 {
+            return Some(Block::new(BlockType::Stone));
+        } else if y >= self.height as isize {
+            return None;
+        }
+
+        let last = self.size as isize - 1;
+        let inside = |x: isize, y: isize, z: isize| {
+            if y >= self.height as isize {
+                None
+            } else {
+                self.cells[((y as usize * self.size) + z as usize) * self.size + x as usize]
+            }
+        };
+
+        let (edge_x, edge_z) = (x.max(0).min(last), z.max(0).min(last));
+        let cell = inside(edge_x, y, edge_z);
+        if (edge_x, edge_z) != (x, z) && inside(edge_x, y + 1, edge_z).is_none() {
+            None
+        } else {
+            cell
+        }
+    }
+
+    /// Returns a chunk with the cells of the chunk at `chunk_y` of the column in the corner of
+    /// its blocks, and the blocks bordering them around them, together with its border blocks and
+    /// the opaque blocks around it.
+    ///
+    /// The rest of the chunk is filled with whatever is further away, so the faces of the cells
+    /// are culled like they would be in the column. Its faces have to be clipped away after
+    /// meshing.
+    fn to_chunk(&self, chunk_y: usize) -> (Box<Chunk>, Box<BorderBlocks>, BorderOccluders) {
+        let base_y = (chunk_y * self.size) as isize;
+        let mut chunk = Box::new(Chunk::default());
+        for (x, y, z) in itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+            chunk.blocks[y][z][x] = self.get(x as isize, base_y + y as isize, z as isize);
+        }
+
+        let (mut border_blocks, border_light, _) = isolated_borders();
+        chunk.light = [[[border_light[0][0][0]; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+
+        // Indexed like in `World::border_blocks`
+        let (before, after) = (-1, CHUNK_ISIZE);
+        for a in 0..CHUNK_SIZE {
+            for b in 0..CHUNK_SIZE {
+                let (ai, bi) = (a as isize, b as isize);
+                border_blocks[0][a][b] = self.get(before, base_y + ai, bi);
+                border_blocks[1][a][b] = self.get(after, base_y + ai, bi);
+                border_blocks[2][a][b] = self.get(bi, base_y + ai, before);
+                border_blocks[3][a][b] = self.get(bi, base_y + ai, after);
+                border_blocks[4][a][b] = self.get(bi, base_y + before, ai);
+                border_blocks[5][a][b] = self.get(bi, base_y + after, ai);
+            }
+        }
+
+        let border_occluders = BorderOccluders::from_fn(|position| {
+            self.get(position.x, base_y + position.y, position.z)
+                .is_some_and(|cell| cell.block_type.is_opaque())
+        });
+
+        (chunk, border_blocks, border_occluders)
+    }
+}
+
+/// Generates (or loads) the chunks of a column and meshes them with cells of `scale`³ blocks.
+///
+/// Returns the meshes of the chunks of the column that have any geometry.
+fn mesh_column(
+    column: Point2<isize>,
+    scale: usize,
+    store: &sled::Db,
+    settings: &Settings,
+) -> Vec<(Point3<isize>, ChunkMesh<ChunkVertex>)> {
+    let chunks: Vec<_> = (0..WORLD_HEIGHT)
+        .map(|y| {
+            let position = Point3::new(column.x, y, column.y);
+            let mut chunk = Box::new(Chunk::default());
+            if let Err(error) = chunk.load(position, store) {
+                eprintln!("Failed to load/generate chunk {:?}: {:?}", position, error);
+            }
+            chunk
+        })
+        .collect();
+    let cells = LodCells::new(&chunks, scale);
+    drop(chunks);
+
+    let (_, border_light, _) = isolated_borders();
+    let size = cells.size as isize;
+    let mut meshes = Vec::new();
+    for chunk_y in 0..WORLD_HEIGHT {
+        let position = Point3::new(column.x, chunk_y, column.y);
+        let (chunk, border_blocks, border_occluders) = cells.to_chunk(chunk_y as usize);
+
+        // Clip away the faces outside of the cells, and scale the rest up to blocks
+        let origin = position * CHUNK_ISIZE;
+        let quads: Vec<_> = chunk
+            .to_quads(
+                Point3::new(0, 0, 0),
+                &border_blocks,
+                &border_light,
+                &border_occluders,
+                settings,
+            )
+            .into_iter()
+            .filter(|quad| {
+                quad.position.x < size && quad.position.y < size && quad.position.z < size
+            })
+            .map(|mut quad| {
+                quad.dx = quad.dx.min(size - quad.position.x) * scale as isize;
+                quad.dz = quad.dz.min(size - quad.position.z) * scale as isize;
+                quad.height = quad.height.min((size - quad.position.y) as f32) * scale as f32;
+                quad.position = origin + quad.position.to_vec() * scale as isize;
+                quad
+            })
+            .collect();
+
+        if !quads.is_empty() {
+            meshes.push((
+                position,
+                ChunkMesh::from_quads(quads, settings).pack(position),
+            ));
+        }
+    }
+    meshes
+}
@@ -1,36 +1,69 @@
 pub mod block;
+pub mod block_breaking;
+pub mod block_shape;
 pub mod chunk;
+pub mod chunk_arena;
+pub mod chunk_mesher;
 pub mod face_flags;
+pub mod falling_block;
+pub mod far_terrain;
+pub mod fluid;
+pub mod grass;
+pub mod light;
+pub mod lod;
 pub mod npc;
 pub mod quad;
+pub mod selection_outline;
+pub mod tick_scheduler;
+pub mod visibility;
 
 use std::{
     collections::VecDeque,
+    ops::Range,
     time::{Duration, Instant},
 };
 
 use crate::{
     camera::Camera,
+    geometry::Geometry,
+    geometry_buffers::GeometryBuffers,
     render_context::RenderContext,
+    settings::Settings,
     texture::Texture,
     time::Time,
-    vertex::{BlockVertex, Vertex},
+    vertex::{BlockVertex, ChunkInstance, ChunkVertex, Vertex},
     view::View,
     world::{
-        block::{Block, BlockType},
-        chunk::{Chunk, CHUNK_ISIZE, CHUNK_SIZE},
+        block::{Block, BlockType, Facing, RenderClass},
+        block_breaking::BlockBreaking,
+        chunk::{BorderBlocks, BorderOccluders, Chunk, CHUNK_ISIZE, CHUNK_SIZE},
+        chunk_arena::ChunkArena,
+        chunk_mesher::ChunkMesher,
+        falling_block::FallingBlock,
+        far_terrain::FarTerrain,
+        light::{Light, MAX_LIGHT, NEIGHBOURS},
+        lod::Lod,
         npc::Npc,
+        selection_outline::SelectionOutline,
+        tick_scheduler::TickScheduler,
     },
 };
-use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
-use fxhash::FxHashMap;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+use fxhash::{FxHashMap, FxHashSet};
+use rand::Rng;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BindGroup, Buffer, CommandEncoder, RenderPipeline, SwapChainTexture,
+    BindGroup, Buffer, BufferUsage, CommandEncoder, RenderPipeline, SwapChainTexture,
 };
 
 pub struct World {
     pub render_pipeline: RenderPipeline,
+    pub translucent_render_pipeline: RenderPipeline,
+    /// The pipelines of chunk geometry, which is made of `ChunkVertex`es instead of
+    /// `BlockVertex`es.
+    pub chunk_render_pipeline: RenderPipeline,
+    pub chunk_cutout_render_pipeline: RenderPipeline,
+    pub chunk_translucent_render_pipeline: RenderPipeline,
     pub depth_texture: Texture,
 
     pub time: Time,
@@ -44,17 +77,54 @@ pub struct World {
     pub chunk_save_queue: VecDeque<(Point3<isize>, bool)>,
     pub chunk_load_queue: VecDeque<Point3<isize>>,
     pub chunk_generate_queue: VecDeque<Point3<isize>>,
-    pub chunk_occlusion_position: Option<Point3<isize>>,
+    /// The view projection matrix `chunks_visible` was found with.
+    pub chunk_occlusion_view: Option<Matrix4<f32>>,
     pub chunks_visible: Option<Vec<Point3<isize>>>,
+    /// Chunks that have to be re-meshed at the end of the frame, because a block or the light they
+    /// depend on changed.
+    pub dirty_chunks: FxHashSet<Point3<isize>>,
+    pub chunk_mesher: ChunkMesher,
+    /// The buffers the meshes of all chunks are stored in, including the ones with a lower level
+    /// of detail.
+    pub chunk_arena: ChunkArena,
+    /// The total size of all chunk meshes uploaded to the GPU so far, in bytes.
+    pub chunk_uploaded_bytes: u64,
+    /// The columns of chunks beyond the render distance, drawn with a lower level of detail.
+    pub lod: Lod,
+    /// The heightmap tiles drawn in place of the columns that aren't loaded or meshed yet.
+    pub far_terrain: FarTerrain,
 
     pub highlighted: Option<(Point3<isize>, Vector3<i32>)>,
+    pub selection_outline: SelectionOutline,
+    pub breaking: Option<BlockBreaking>,
+
+    pub tick_scheduler: TickScheduler,
+
+    pub falling_blocks: Vec<FallingBlock>,
+    pub falling_block_buffers: Option<GeometryBuffers<u16>>,
+    /// The indices of each falling block in `falling_block_buffers`, in the same order.
+    pub falling_block_indices: Vec<Range<u32>>,
+
+    pub render_stats: RenderStats,
 
     pub unload_timer: Duration,
 }
 
+/// How many chunks and entities were drawn and culled by frustum culling in the last frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub chunks_drawn: usize,
+    pub chunks_culled: usize,
+    pub entities_drawn: usize,
+    pub entities_culled: usize,
+}
+
 pub const RENDER_DISTANCE: isize = 8;
 pub const WORLD_HEIGHT: isize = 16 * 16 / CHUNK_ISIZE;
 
+/// The number of random blocks in every loaded chunk that are randomly ticked each tick.
+const RANDOM_TICKS_PER_CHUNK: usize = 24;
+
 const DEBUG_IO: bool = false;
 
 impl World {
@@ -74,6 +144,10 @@ impl World {
         );
 
         self.update_highlight(render_context, camera);
+        for _ in 0..self.tick_scheduler.advance(dt) {
+            self.tick();
+        }
+        self.update_falling_blocks(render_context, dt);
 
         // Queue up new chunks for loading, if necessary
         let camera_pos: Point3<isize> = camera.position.cast().unwrap();
@@ -129,14 +203,14 @@ impl World {
                         eprintln!("Failed to load/generate chunk {:?}: {:?}", position, error)
                     }
                     Ok(true) => {
-                        self.update_chunk_geometry(render_context, position);
+                        self.chunk_loaded(position);
                         self.enqueue_chunk_save(position, false);
                         if DEBUG_IO {
                             println!("Generated chunk {:?}", position);
                         }
                     }
                     Ok(false) => {
-                        self.update_chunk_geometry(render_context, position);
+                        self.chunk_loaded(position);
                         if DEBUG_IO {
                             println!("Loaded chunk {:?}", position);
                         }
@@ -170,8 +244,13 @@ impl World {
         }
 
         if chunk_updates > 0 {
-            self.chunk_occlusion_position = None;
+            self.chunk_occlusion_view = None;
         }
+
+        self.update_dirty_chunks(render_context);
+        self.upload_chunk_meshes(render_context);
+        lod::update(self, render_context, camera_chunk);
+        self.far_terrain.update(render_context, camera_chunk);
     }
 
     pub fn render<'a>(
@@ -184,6 +263,74 @@ impl World {
         // TODO Move this to update
         self.update_occlusion(view);
 
+        // Frustum culling is done up front, to count what's drawn for the debug HUD
+        let visible = self.chunks_visible.as_ref().unwrap();
+        let in_view: Vec<_> = visible
+            .iter()
+            .copied()
+            .filter(|position| Chunk::is_visible(position * CHUNK_ISIZE, view))
+            .collect();
+        let npc_in_view = view.is_visible(&self.npc.aabb);
+        let falling_blocks_in_view: Vec<_> = self
+            .falling_blocks
+            .iter()
+            .zip(&self.falling_block_indices)
+            .filter(|(falling_block, _)| view.is_visible(&falling_block.aabb()))
+            .map(|(_, indices)| indices.clone())
+            .collect();
+
+        let entities_drawn = npc_in_view as usize + falling_blocks_in_view.len();
+        self.render_stats = RenderStats {
+            chunks_drawn: in_view.len(),
+            chunks_culled: visible.len() - in_view.len(),
+            entities_drawn,
+            entities_culled: 1 + self.falling_blocks.len() - entities_drawn,
+        };
+
+        // Translucent geometry is blended onto what's already drawn, so it has to be drawn after
+        // all opaque geometry, from the back to the front.
+        let camera_position = view.camera.position.to_vec();
+        let mut translucent: Vec<_> = in_view
+            .iter()
+            .filter(|position| {
+                self.chunks[position]
+                    .buffers
+                    .as_ref()
+                    .is_some_and(|buffers| !buffers.translucent.is_empty())
+            })
+            .map(|position| {
+                let center = (position * CHUNK_ISIZE).cast::<f32>().unwrap().to_vec()
+                    + Vector3::new(1.0, 1.0, 1.0) * (CHUNK_SIZE as f32 / 2.0);
+                (position, (center - camera_position).magnitude2())
+            })
+            .collect();
+        translucent.sort_unstable_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+
+        // The draws of all chunks are collected up front, so they're written to the GPU at once
+        let (chunks, lod, chunk_arena) = (&self.chunks, &self.lod, &mut self.chunk_arena);
+        chunk_arena.clear_draws();
+        let mut triangle_count = 0;
+        let mut push_draws = |positions: &mut dyn Iterator<Item = &Point3<isize>>,
+                              render_class: RenderClass| {
+            let start = chunk_arena.draw_count();
+            // The columns with a lower level of detail are all further away than the loaded
+            // chunks, so they're drawn first
+            triangle_count += lod.push_draws(chunk_arena, view, chunks, render_class);
+            for position in positions {
+                if let Some(buffers) = &chunks[position].buffers {
+                    triangle_count += chunk_arena.push(buffers, *position, render_class);
+                }
+            }
+            start..chunk_arena.draw_count()
+        };
+        let opaque_draws = push_draws(&mut in_view.iter(), RenderClass::Opaque);
+        let cutout_draws = push_draws(&mut in_view.iter(), RenderClass::Cutout);
+        let translucent_draws = push_draws(
+            &mut translucent.iter().map(|(position, _)| *position),
+            RenderClass::Translucent,
+        );
+        chunk_arena.write_draws(render_context);
+
         let mut render_pass = render_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("render_pass"),
             color_attachments: &[wgpu::RenderPassColorAttachment {
@@ -202,26 +349,56 @@ impl World {
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &self.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    // The depth is reversed, so the far plane is at 0
+                    load: wgpu::LoadOp::Clear(0.0),
                     store: true,
                 }),
                 stencil_ops: None,
             }),
         });
-        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_pipeline(&self.chunk_render_pipeline);
 
         let texture_manager = render_context.texture_manager.as_ref().unwrap();
         render_pass.set_bind_group(0, texture_manager.bind_group.as_ref().unwrap(), &[]);
         render_pass.set_bind_group(1, &view.bind_group, &[]);
         render_pass.set_bind_group(2, &self.time_bind_group, &[]);
+        self.chunk_arena.render(&mut render_pass, opaque_draws);
+
+        // Columns without any loaded chunks are drawn as tiles of the heightmap. A tile would
+        // z-fight with the meshes of a partly loaded column, so it's hidden as soon as the first
+        // chunk of the column is loaded.
+        let (chunks, lod) = (&self.chunks, &self.lod);
+        triangle_count += self.far_terrain.render(&mut render_pass, view, |column| {
+            lod.columns.contains_key(&column)
+                || (0..WORLD_HEIGHT)
+                    .any(|y| chunks.contains_key(&Point3::new(column.x, y, column.y)))
+        });
 
-        let visible = self.chunks_visible.as_ref().unwrap();
-        let mut triangle_count = 0;
-        for position in visible {
-            let chunk = self.chunks.get(position).unwrap();
-            triangle_count += chunk.render(&mut render_pass, &position, view);
+        render_pass.set_pipeline(&self.render_pipeline);
+        if npc_in_view {
+            triangle_count += self.npc.render(&mut render_pass);
+        }
+        if let Some(buffers) = &self.falling_block_buffers {
+            buffers.apply_buffers(&mut render_pass);
+            for indices in falling_blocks_in_view {
+                triangle_count += indices.len() / 3;
+                render_pass.draw_indexed(indices, 0, 0..1);
+            }
+        }
+
+        render_pass.set_pipeline(&self.chunk_cutout_render_pipeline);
+        self.chunk_arena.render(&mut render_pass, cutout_draws);
+
+        render_pass.set_pipeline(&self.translucent_render_pipeline);
+        if let Some(breaking) = &self.breaking {
+            breaking.buffers.apply_buffers(&mut render_pass);
+            triangle_count += breaking.buffers.draw_indexed(&mut render_pass);
         }
-        triangle_count += self.npc.render(&mut render_pass);
+        render_pass.set_pipeline(&self.chunk_translucent_render_pipeline);
+        self.chunk_arena.render(&mut render_pass, translucent_draws);
+
+        self.selection_outline.render(&mut render_pass, view);
+
         triangle_count
     }
 
@@ -293,52 +470,92 @@ impl World {
             &(wgpu::ShaderModuleDescriptor {
                 label: Some("shader"),
                 flags: wgpu::ShaderFlags::all(),
-                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/world.wgsl").into()),
+                source: wgpu::ShaderSource::Wgsl(
+                    (ChunkVertex::tint_wgsl() + include_str!("../shaders/world.wgsl")).into(),
+                ),
             }),
         );
 
-        let render_pipeline =
-            render_context
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Render Pipeline"),
-                    layout: Some(&render_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &shader,
-                        entry_point: "main",
-                        buffers: &[BlockVertex::descriptor()],
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &shader,
-                        entry_point: "main",
-                        targets: &[wgpu::ColorTargetState {
-                            format: render_context.swap_chain_descriptor.format,
-                            blend: Some(wgpu::BlendState {
-                                alpha: wgpu::BlendComponent::REPLACE,
-                                color: wgpu::BlendComponent::REPLACE,
-                            }),
-                            write_mask: wgpu::ColorWrite::ALL,
-                        }],
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        cull_mode: Some(wgpu::Face::Back),
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        ..Default::default()
-                    },
-                    depth_stencil: Some(wgpu::DepthStencilState {
-                        format: Texture::DEPTH_FORMAT,
-                        depth_write_enabled: true,
-                        depth_compare: wgpu::CompareFunction::Less,
-                        stencil: wgpu::StencilState::default(),
-                        bias: wgpu::DepthBiasState::default(),
-                    }),
-                    multisample: wgpu::MultisampleState::default(),
-                });
+        let block_vertex_buffers = [BlockVertex::descriptor()];
+        let block_vertex = wgpu::VertexState {
+            module: &shader,
+            entry_point: "main",
+            buffers: &block_vertex_buffers,
+        };
+        let chunk_vertex_buffers = [ChunkVertex::descriptor(), ChunkInstance::descriptor()];
+        let chunk_vertex = wgpu::VertexState {
+            module: &shader,
+            entry_point: "main_chunk",
+            buffers: &chunk_vertex_buffers,
+        };
+
+        let render_pipeline = Self::create_render_pipeline(
+            render_context,
+            "Render Pipeline",
+            &render_pipeline_layout,
+            block_vertex.clone(),
+            "main",
+            wgpu::BlendState {
+                alpha: wgpu::BlendComponent::REPLACE,
+                color: wgpu::BlendComponent::REPLACE,
+            },
+            true,
+        );
+
+        let translucent_render_pipeline = Self::create_render_pipeline(
+            render_context,
+            "Translucent Render Pipeline",
+            &render_pipeline_layout,
+            block_vertex,
+            "main",
+            wgpu::BlendState::ALPHA_BLENDING,
+            false,
+        );
+
+        let chunk_render_pipeline = Self::create_render_pipeline(
+            render_context,
+            "Chunk Render Pipeline",
+            &render_pipeline_layout,
+            chunk_vertex.clone(),
+            "main",
+            wgpu::BlendState {
+                alpha: wgpu::BlendComponent::REPLACE,
+                color: wgpu::BlendComponent::REPLACE,
+            },
+            true,
+        );
+
+        let chunk_cutout_render_pipeline = Self::create_render_pipeline(
+            render_context,
+            "Chunk Cutout Render Pipeline",
+            &render_pipeline_layout,
+            chunk_vertex.clone(),
+            "main_cutout",
+            wgpu::BlendState {
+                alpha: wgpu::BlendComponent::REPLACE,
+                color: wgpu::BlendComponent::REPLACE,
+            },
+            true,
+        );
+
+        let chunk_translucent_render_pipeline = Self::create_render_pipeline(
+            render_context,
+            "Chunk Translucent Render Pipeline",
+            &render_pipeline_layout,
+            chunk_vertex,
+            "main",
+            wgpu::BlendState::ALPHA_BLENDING,
+            false,
+        );
 
         let depth_texture = Texture::create_depth_texture(render_context, "depth_texture");
 
         Self {
             render_pipeline,
+            translucent_render_pipeline,
+            chunk_render_pipeline,
+            chunk_cutout_render_pipeline,
+            chunk_translucent_render_pipeline,
 
             time,
             time_buffer,
@@ -353,35 +570,102 @@ impl World {
             chunk_load_queue: VecDeque::new(),
             chunk_save_queue: VecDeque::new(),
             chunk_generate_queue: VecDeque::new(),
-            chunk_occlusion_position: None,
+            chunk_occlusion_view: None,
             chunks_visible: None,
+            dirty_chunks: FxHashSet::default(),
+            chunk_mesher: ChunkMesher::default(),
+            chunk_arena: ChunkArena::new(render_context),
+            chunk_uploaded_bytes: 0,
+            lod: Lod::default(),
+            far_terrain: FarTerrain::new(render_context, view),
 
             highlighted: None,
+            selection_outline: SelectionOutline::new(render_context, view),
+            breaking: None,
+
+            tick_scheduler: TickScheduler::default(),
+
+            falling_blocks: Vec::new(),
+            falling_block_buffers: None,
+            falling_block_indices: Vec::new(),
+
+            render_stats: RenderStats::default(),
 
             unload_timer: Duration::ZERO,
         }
     }
 
+    /// Creates a pipeline that draws with the given vertex stage, and the fragment stage of the
+    /// same shader module.
+    fn create_render_pipeline(
+        render_context: &RenderContext,
+        label: &str,
+        layout: &wgpu::PipelineLayout,
+        vertex: wgpu::VertexState,
+        fragment_entry_point: &str,
+        blend: wgpu::BlendState,
+        depth_write_enabled: bool,
+    ) -> RenderPipeline {
+        let shader = vertex.module;
+        render_context
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex,
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: fragment_entry_point,
+                    targets: &[wgpu::ColorTargetState {
+                        format: render_context.swap_chain_descriptor.format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrite::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled,
+                    depth_compare: wgpu::CompareFunction::Greater,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+            })
+    }
+
+    /// Finds the loaded chunks that might be visible from the chunk the camera is in.
+    ///
+    /// This is a breadth-first search from the camera, which only ever moves away from it: a chunk
+    /// is left through a face in a direction the search hasn't come from yet, and only if that
+    /// face can be seen from the face the chunk was entered through, according to its
+    /// `ChunkVisibility`. Chunks behind walls of solid blocks, like caves seen from the surface,
+    /// are never reached. Neither are the chunks behind the ones outside the frustum, as the search
+    /// doesn't continue past those.
     pub fn update_occlusion(&mut self, view: &View) {
         let initial_position = view
             .camera
             .position
             .map(|x| (x.floor() as isize).div_euclid(CHUNK_ISIZE));
 
-        if self.chunk_occlusion_position == Some(initial_position) {
+        if self.chunk_occlusion_view == Some(view.projection_matrix()) {
             return;
         }
 
-        self.chunk_occlusion_position = Some(initial_position);
-        let mut queue = VecDeque::from(vec![initial_position]);
+        self.chunk_occlusion_view = Some(view.projection_matrix());
+        // The chunks to visit, with the face they're entered through and a mask of the sides
+        // the search moved towards to reach them. The camera can look through any face.
+        let mut queue = VecDeque::from(vec![(initial_position, None, 0u8)]);
 
         assert_eq!(CHUNK_SIZE, 32);
         let mut visited = [0u32; CHUNK_SIZE * CHUNK_SIZE];
         let mut render_queue = Vec::new();
 
-        while !queue.is_empty() {
-            let position = queue.pop_front().unwrap();
-
+        while let Some((position, entered, directions)) = queue.pop_front() {
             let b = position.map(|x| x.rem_euclid(CHUNK_ISIZE) as usize);
             if (visited[b.x * CHUNK_SIZE + b.y] >> b.z) & 1 == 1 {
                 continue;
@@ -390,15 +674,25 @@ impl World {
 
             if let Some(chunk) = self.chunks.get(&position) {
                 render_queue.push(position);
-                if !chunk.full {
-                    queue.extend([
-                        position + Vector3::unit_x(),
-                        position - Vector3::unit_x(),
-                        position + Vector3::unit_y(),
-                        position - Vector3::unit_y(),
-                        position + Vector3::unit_z(),
-                        position - Vector3::unit_z(),
-                    ]);
+                // Chunks outside the frustum are still listed, so they're counted as culled
+                if position != initial_position && !Chunk::is_visible(position * CHUNK_ISIZE, view)
+                {
+                    continue;
+                }
+
+                for (side, offset) in NEIGHBOURS.iter().enumerate() {
+                    // The sides come in pairs of opposites, like left and right
+                    let opposite = side ^ 1;
+                    if (directions >> opposite) & 1 == 1 {
+                        continue;
+                    }
+                    if let Some(entered) = entered {
+                        if !chunk.visibility.is_connected(entered, side) {
+                            continue;
+                        }
+                    }
+
+                    queue.push_back((position + offset, Some(opposite), directions | 1 << side));
                 }
             }
         }
@@ -418,55 +712,312 @@ impl World {
         }
     }
 
-    pub fn update_chunk_geometry(
-        &mut self,
-        render_context: &RenderContext,
-        chunk_position: Point3<isize>,
-    ) {
+    /// Lights a chunk that finished loading and marks it and its neighbours as dirty, since the
+    /// faces of the neighbours bordering it may now be hidden. Water in or next to it that can
+    /// flow is scheduled to do so.
+    fn chunk_loaded(&mut self, chunk_position: Point3<isize>) {
+        let dirty_chunks = light::chunk_loaded(self, chunk_position);
+        self.dirty_chunks.extend(dirty_chunks);
+        self.dirty_chunks.insert(chunk_position);
+        for offset in &NEIGHBOURS {
+            self.dirty_chunks.insert(chunk_position + offset);
+        }
+
+        for position in fluid::chunk_loaded(self, chunk_position) {
+            self.schedule_block_update(position);
+        }
+    }
+
+    /// Returns the blocks bordering the chunk at `chunk_position`. The blocks in unloaded chunks
+    /// are air, so the faces next to them are visible.
+    fn border_blocks(&self, chunk_position: Point3<isize>) -> Box<BorderBlocks> {
+        let mut border = Box::new([[[None; CHUNK_SIZE]; CHUNK_SIZE]; 6]);
+        for (side, offset) in NEIGHBOURS.iter().enumerate() {
+            let chunk = match self.chunks.get(&(chunk_position + offset)) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+
+            // The layer of the neighbouring chunk that touches this chunk
+            let layer = |offset: isize| if offset < 0 { CHUNK_SIZE - 1 } else { 0 };
+            for a in 0..CHUNK_SIZE {
+                for b in 0..CHUNK_SIZE {
+                    border[side][a][b] = if offset.x != 0 {
+                        chunk.blocks[a][b][layer(offset.x)]
+                    } else if offset.z != 0 {
+                        chunk.blocks[a][layer(offset.z)][b]
+                    } else {
+                        chunk.blocks[layer(offset.y)][a][b]
+                    };
+                }
+            }
+        }
+        border
+    }
+
+    /// Returns which blocks around the chunk at `chunk_position` are opaque, including the ones in
+    /// the chunks diagonal to it. The blocks in unloaded chunks are air.
+    fn border_occluders(&self, chunk_position: Point3<isize>) -> BorderOccluders {
+        let origin = chunk_position * CHUNK_ISIZE;
+        BorderOccluders::from_fn(|position| {
+            self.get_block(origin + position)
+                .is_some_and(|block| block.block_type.is_opaque())
+        })
+    }
+
+    /// Queues the chunk at `chunk_position` to be meshed on a worker thread, using a snapshot of
+    /// its current blocks and light.
+    fn enqueue_chunk_mesh(&mut self, chunk_position: Point3<isize>, settings: Settings) {
+        let border_blocks = self.border_blocks(chunk_position);
+        let border_light = light::border_light(self, chunk_position);
+        let border_occluders = self.border_occluders(chunk_position);
         let chunk = self.chunks.get_mut(&chunk_position).unwrap();
-        chunk.update_geometry(render_context, chunk_position, self.highlighted);
+        chunk.mesh_version = self.chunk_mesher.spawn(
+            chunk_position,
+            chunk.snapshot(),
+            border_blocks,
+            border_light,
+            border_occluders,
+            settings,
+        );
     }
 
-    fn update_highlight(&mut self, render_context: &RenderContext, camera: &Camera) {
-        let old = self.highlighted;
-        let new = self.raycast(camera.position, camera.direction());
+    /// Uploads the meshes the worker threads finished to the GPU. Meshes of chunks that changed
+    /// or were unloaded since they were queued are dropped.
+    fn upload_chunk_meshes(&mut self, render_context: &RenderContext) {
+        let mut uploaded = false;
+        for result in self.chunk_mesher.finished() {
+            if let Some(chunk) = self.chunks.get_mut(&result.position) {
+                if chunk.mesh_version == result.version {
+                    chunk.upload_mesh(render_context, &mut self.chunk_arena, &result.mesh);
+                    chunk.visibility = result.visibility;
+                    self.chunk_uploaded_bytes += chunk.buffer_size();
+                    uploaded = true;
+                }
+            }
+        }
+
+        if uploaded {
+            // The visibility of the chunks might have changed
+            self.chunk_occlusion_view = None;
+        }
+    }
 
-        let old_chunk = old.map(|(pos, _)| pos.map(|n| n.div_euclid(CHUNK_ISIZE)));
-        let new_chunk = new.map(|(pos, _)| pos.map(|n| n.div_euclid(CHUNK_ISIZE)));
+    /// Returns the combined size of the buffers of all chunks, including the ones with a lower
+    /// level of detail, in bytes.
+    pub fn chunk_buffer_size(&self) -> wgpu::BufferAddress {
+        self.chunks.values().map(Chunk::buffer_size).sum::<u64>()
+            + self.lod.buffer_size()
+            + self.far_terrain.buffer_size()
+    }
 
-        if old != new {
-            self.highlighted = new;
+    /// Runs a single world tick, updating the scheduled blocks and random blocks in every chunk.
+    fn tick(&mut self) {
+        let mut changes = Vec::new();
+        for position in self.tick_scheduler.next_tick() {
+            self.scheduled_tick(position, &mut changes);
+        }
+        self.random_ticks(&mut changes);
 
-            if let Some(old_chunk_) = old_chunk {
-                self.update_chunk_geometry(render_context, old_chunk_);
+        self.set_blocks(changes);
+    }
+
+    fn scheduled_tick(
+        &mut self,
+        position: Point3<isize>,
+        changes: &mut Vec<(Point3<isize>, Option<Block>)>,
+    ) {
+        let block = match self.get_block(position) {
+            Some(block) => *block,
+            None => return,
+        };
+
+        if block.block_type == BlockType::Water {
+            // Next to a chunk that isn't loaded, the water is paused until `fluid::chunk_loaded`
+            // finds it once the chunk is
+            if let Some(mut position_changes) = fluid::update(self, position) {
+                changes.append(&mut position_changes);
+            }
+        } else if block.block_type.has_gravity() {
+            match FallingBlock::should_fall(self, position) {
+                Some(true) => {
+                    self.falling_blocks.push(FallingBlock::new(block, position));
+                    changes.push((position, None));
+                }
+                Some(false) => (),
+                None => self.schedule_block_update(position),
             }
+        }
+    }
 
-            if let Some(new_chunk_) = new_chunk {
-                // Don't update the same chunk twice
-                if old_chunk != new_chunk {
-                    self.update_chunk_geometry(render_context, new_chunk_);
+    fn random_ticks(&self, changes: &mut Vec<(Point3<isize>, Option<Block>)>) {
+        let mut rng = rand::thread_rng();
+        for (chunk_position, chunk) in &self.chunks {
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let x = rng.gen_range(0, CHUNK_SIZE);
+                let y = rng.gen_range(0, CHUNK_SIZE);
+                let z = rng.gen_range(0, CHUNK_SIZE);
+
+                let block = match chunk.blocks[y][z][x] {
+                    Some(block) if block.block_type.has_random_ticks() => block,
+                    _ => continue,
+                };
+
+                let position =
+                    chunk_position * CHUNK_ISIZE + Vector3::new(x as isize, y as isize, z as isize);
+                if block.block_type == BlockType::Grass {
+                    changes.append(&mut grass::random_tick(self, position));
                 }
             }
         }
     }
 
-    pub fn break_at_crosshair(&mut self, render_context: &RenderContext, camera: &Camera) {
+    /// Schedules a tick for the block at `position`, if it reacts to its neighbours changing.
+    fn schedule_block_update(&mut self, position: Point3<isize>) {
+        let delay = self
+            .get_block(position)
+            .and_then(|block| block.block_type.update_delay());
+        if let Some(delay) = delay {
+            self.tick_scheduler.schedule(position, delay);
+        }
+    }
+
+    fn update_falling_blocks(&mut self, render_context: &RenderContext, dt: Duration) {
+        if self.falling_blocks.is_empty() && self.falling_block_buffers.is_none() {
+            return;
+        }
+
+        // The lowest blocks move first, and blocks are placed as soon as they land, so the blocks
+        // above land on top of them instead of in the same place
+        let mut falling_blocks = std::mem::take(&mut self.falling_blocks);
+        falling_blocks.sort_unstable_by(|a, b| a.position.y.partial_cmp(&b.position.y).unwrap());
+        for mut falling_block in falling_blocks {
+            match falling_block.update(dt, self) {
+                Some(position) => self.set_block(
+                    position.x,
+                    position.y,
+                    position.z,
+                    Some(falling_block.block),
+                ),
+                None => self.falling_blocks.push(falling_block),
+            }
+        }
+
+        self.falling_block_buffers = if self.falling_blocks.is_empty() {
+            None
+        } else {
+            let mut geometry = Geometry::new(Vec::new(), Vec::new());
+            self.falling_block_indices.clear();
+            for falling_block in &self.falling_blocks {
+                let position = falling_block.position.map(|x| x.floor() as isize);
+                let light = light::get(self, position).unwrap_or(Light::new(MAX_LIGHT, 0));
+                let mut block_geometry =
+                    falling_block.to_geometry(light, geometry.vertices.len() as u16);
+                let start = geometry.indices.len() as u32;
+                geometry.append(&mut block_geometry);
+                self.falling_block_indices
+                    .push(start..geometry.indices.len() as u32);
+            }
+            Some(GeometryBuffers::from_geometry(
+                render_context,
+                &geometry,
+                BufferUsage::empty(),
+            ))
+        };
+    }
+
+    /// Sets all the given blocks.
+    fn set_blocks(&mut self, changes: Vec<(Point3<isize>, Option<Block>)>) {
+        for (position, block) in changes {
+            self.set_block(position.x, position.y, position.z, block);
+        }
+    }
+
+    /// Queues the loaded chunks in `dirty_chunks` for meshing, in a single batch.
+    fn update_dirty_chunks(&mut self, render_context: &RenderContext) {
+        for chunk_position in std::mem::take(&mut self.dirty_chunks) {
+            if self.chunks.contains_key(&chunk_position) {
+                self.enqueue_chunk_mesh(chunk_position, render_context.settings);
+            }
+        }
+    }
+
+    /// Queues every loaded chunk for meshing, e.g. because a setting that affects meshes changed.
+    pub fn update_all_chunk_geometry(&mut self, render_context: &RenderContext) {
+        let positions: Vec<_> = self.chunks.keys().copied().collect();
+        for position in positions {
+            self.enqueue_chunk_mesh(position, render_context.settings);
+        }
+    }
+
+    fn update_highlight(&mut self, render_context: &RenderContext, camera: &Camera) {
+        self.highlighted = self.raycast(camera.position, camera.direction());
+
+        let selected = self
+            .highlighted
+            .and_then(|(position, _)| Some((position, *self.get_block(position)?)));
+        self.selection_outline.update(render_context, selected);
+    }
+
+    /// Breaks the highlighted block over time while `pressed` is true, based on its hardness.
+    ///
+    /// The progress is reset when the button is released or the highlighted block changes.
+    pub fn update_breaking(&mut self, render_context: &RenderContext, dt: Duration, pressed: bool) {
+        let target = match self.highlighted {
+            Some((position, _)) if pressed => position,
+            _ => {
+                self.breaking = None;
+                return;
+            }
+        };
+
+        let block = match self.get_block(target) {
+            Some(block) => *block,
+            None => {
+                self.breaking = None;
+                return;
+            }
+        };
+        let hardness = match block.block_type.hardness() {
+            Some(hardness) => hardness,
+            None => {
+                self.breaking = None;
+                return;
+            }
+        };
+
+        if self.breaking.as_ref().map(|breaking| breaking.position) != Some(target) {
+            let light = light::face_light(self, target);
+            self.breaking = Some(BlockBreaking::new(render_context, &block, target, light));
+        }
+
+        let progress = if hardness > 0.0 {
+            dt.as_secs_f32() / hardness
+        } else {
+            1.0
+        };
+        let breaking = self.breaking.as_mut().unwrap();
+        if breaking.progress(render_context, &block, progress) {
+            self.breaking = None;
+            self.set_blocks(vec![(target, None)]);
+        }
+    }
+
+    pub fn break_at_crosshair(&mut self, camera: &Camera) {
         if let Some((pos, _)) = self.raycast(camera.position, camera.direction()) {
             self.set_block(pos.x as isize, pos.y as isize, pos.z as isize, None);
-            self.update_chunk_geometry(render_context, pos / CHUNK_ISIZE);
         }
     }
 
-    pub fn place_at_crosshair(
-        &mut self,
-        render_context: &RenderContext,
-        camera: &Camera,
-        block_type: BlockType,
-    ) {
+    pub fn place_at_crosshair(&mut self, camera: &Camera, block_type: BlockType) {
         if let Some((pos, face_normal)) = self.raycast(camera.position, camera.direction()) {
             let new_pos = (pos.cast().unwrap() + face_normal).cast().unwrap();
-            self.set_block(new_pos.x, new_pos.y, new_pos.z, Some(Block { block_type }));
-            self.update_chunk_geometry(render_context, pos / CHUNK_ISIZE);
+            let direction = camera.direction();
+            let block = Block {
+                facing: Facing::from_direction(direction.x, direction.z),
+                ..Block::new(block_type)
+            };
+            self.set_block(new_pos.x, new_pos.y, new_pos.z, Some(block));
         }
     }
 
@@ -487,11 +1038,39 @@ impl World {
             z.div_euclid(CHUNK_ISIZE),
         );
 
+        let position = Point3::new(x, y, z);
         if let Some(chunk) = self.chunks.get_mut(&chunk_position) {
             let bx = x.rem_euclid(CHUNK_ISIZE) as usize;
             let by = y.rem_euclid(CHUNK_ISIZE) as usize;
             let bz = z.rem_euclid(CHUNK_ISIZE) as usize;
-            chunk.blocks[by][bz][bx] = block;
+            let old_block = std::mem::replace(&mut chunk.blocks[by][bz][bx], block);
+
+            let was_opaque = old_block.is_some_and(|block| block.block_type.is_opaque());
+            let old_emission = old_block.map_or(0, |block| block.block_type.light_emission());
+            let dirty_chunks = light::block_changed(self, position, was_opaque, old_emission);
+            self.dirty_chunks.extend(dirty_chunks);
+
+            // The block is part of the mesh of its own chunk, and of the border of the chunks
+            // next to it
+            self.dirty_chunks.insert(chunk_position);
+            for offset in &NEIGHBOURS {
+                self.dirty_chunks
+                    .insert((position + offset).map(|x| x.div_euclid(CHUNK_ISIZE)));
+            }
+        }
+
+        // Blocks next to the block might have to react to it, e.g. fluids flowing into it or
+        // sand falling down
+        for position in [
+            position,
+            position + Vector3::unit_x(),
+            position - Vector3::unit_x(),
+            position + Vector3::unit_y(),
+            position - Vector3::unit_y(),
+            position + Vector3::unit_z(),
+            position - Vector3::unit_z(),
+        ] {
+            self.schedule_block_update(position);
         }
 
         self.enqueue_chunk_save(chunk_position, false);
@@ -559,7 +1138,8 @@ impl World {
                 return None;
             }
 
-            if self.get_block(position.cast().unwrap()).is_some() {
+            let block = self.get_block(position.cast().unwrap());
+            if block.is_some_and(|block| block.block_type.is_selectable()) {
                 // Intersection occurred
                 return Some((position.cast().unwrap(), face));
             }
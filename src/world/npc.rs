@@ -1,12 +1,12 @@
 extern crate gltf;
 extern crate wgpu;
 
-use cgmath::Vector3;
+use cgmath::{Point3, Vector3};
 use wgpu::{BufferUsage, RenderPass};
 
 use crate::{
-    geometry::Geometry, geometry_buffers::GeometryBuffers, render_context::RenderContext,
-    vertex::BlockVertex,
+    aabb::Aabb, geometry::Geometry, geometry_buffers::GeometryBuffers,
+    render_context::RenderContext, vertex::BlockVertex,
 };
 
 pub struct Npc {
@@ -15,6 +15,8 @@ pub struct Npc {
     pub rotation: Vector3<f32>,
     pub geometry: Geometry<BlockVertex, u32>,
     pub geometry_buffers: Option<GeometryBuffers<u32>>,
+    /// The bounds of the geometry, for frustum culling.
+    pub aabb: Aabb,
 }
 
 impl Npc {
@@ -45,9 +47,10 @@ impl Npc {
                         position,
                         texture_coordinates,
                         normal,
-                        highlighted: 0,
                         texture_id: 0,
                         color: [1.0, 1.0, 1.0, 1.0],
+                        light: [1.0, 0.0],
+                        ambient_occlusion: 1.0,
                     };
 
                     vertices.push(current_vert);
@@ -55,11 +58,24 @@ impl Npc {
             }
         }
 
+        let mut aabb = Aabb {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        };
+        for vertex in &vertices {
+            let position = Point3::from(vertex.position);
+            aabb = aabb.union(&Aabb {
+                min: position,
+                max: position,
+            });
+        }
+
         Self {
             position,
             scale,
             rotation,
             geometry: Geometry::new(vertices, indices),
+            aabb,
             geometry_buffers: None,
         }
     }
@@ -78,3 +94,9 @@ impl Npc {
         buffers.draw_indexed(render_pass)
     }
 }
+
+impl Default for Npc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,9 +1,14 @@
-use cgmath::{Point3, Vector3, Vector4, Zero};
+use cgmath::{Point3, Vector3, Vector4};
 
 use crate::{
     geometry::Geometry,
     vertex::BlockVertex,
-    world::{block::BlockType, face_flags::*},
+    world::{
+        block::{BlockType, Facing},
+        block_shape::BlockShape,
+        face_flags::*,
+        light::{Light, MAX_LIGHT},
+    },
 };
 
 #[derive(Debug)]
@@ -11,23 +16,42 @@ pub struct Quad {
     pub position: Point3<isize>,
     pub dx: isize,
     pub dz: isize,
+    /// The height of the quad, in blocks. Lower than 1 for e.g. flowing water.
+    pub height: f32,
 
-    pub highlighted_normal: Vector3<i32>,
     pub visible_faces: FaceFlags,
     pub block_type: Option<BlockType>,
+    /// The `Facing` of the block the quad describes. Only used by shapes that aren't cubes.
+    pub facing: Facing,
+    /// The light next to each face of the quad, in the same order as `BlockType::texture_indices`.
+    pub light: [Light; 6],
+    /// The ambient occlusion of the corners of each face, from 0 (fully occluded) to 3 (not
+    /// occluded). The faces are in the same order as `light`, the corners as in `FACE_CORNERS`.
+    pub ao: [[u8; 4]; 6],
 }
 
+/// The corners of each face of a unit quad, in the order `Quad::to_geometry` emits their vertices.
+/// The faces are in the same order as `BlockType::texture_indices`.
+#[rustfmt::skip]
+pub const FACE_CORNERS: [[Vector3<isize>; 4]; 6] = [
+    [Vector3::new(0, 0, 0), Vector3::new(0, 0, 1), Vector3::new(0, 1, 1), Vector3::new(0, 1, 0)],
+    [Vector3::new(1, 0, 0), Vector3::new(1, 0, 1), Vector3::new(1, 1, 1), Vector3::new(1, 1, 0)],
+    [Vector3::new(0, 0, 0), Vector3::new(0, 1, 0), Vector3::new(1, 1, 0), Vector3::new(1, 0, 0)],
+    [Vector3::new(0, 0, 1), Vector3::new(0, 1, 1), Vector3::new(1, 1, 1), Vector3::new(1, 0, 1)],
+    [Vector3::new(0, 0, 0), Vector3::new(0, 0, 1), Vector3::new(1, 0, 1), Vector3::new(1, 0, 0)],
+    [Vector3::new(0, 1, 0), Vector3::new(0, 1, 1), Vector3::new(1, 1, 1), Vector3::new(1, 1, 0)],
+];
+
+/// The brightness of a vertex for each ambient occlusion value.
+const AO_BRIGHTNESS: [f32; 4] = [0.5, 0.65, 0.8, 1.0];
+
 impl Quad {
     pub fn new(position: Point3<isize>, dx: isize, dz: isize) -> Self {
         Quad {
             position,
             dx,
             dz,
-
-            /// The normal of the face that was highlighted.
-            ///
-            /// Set to Vector3::zero if no faces are highlighted.
-            highlighted_normal: Vector3::zero(),
+            height: 1.0,
 
             /// Bitmap of the visible faces.
             visible_faces: FACE_ALL,
@@ -36,7 +60,36 @@ impl Quad {
             ///
             /// Used for determining which texture to map to it. When `None`, texture index 0 will be used.
             block_type: None,
+
+            facing: Facing::default(),
+            light: [Light::new(MAX_LIGHT, 0); 6],
+            ao: [[3; 4]; 6],
+        }
+    }
+
+    fn ao_brightness(ao: [u8; 4]) -> [f32; 4] {
+        [
+            AO_BRIGHTNESS[ao[0] as usize],
+            AO_BRIGHTNESS[ao[1] as usize],
+            AO_BRIGHTNESS[ao[2] as usize],
+            AO_BRIGHTNESS[ao[3] as usize],
+        ]
+    }
+
+    /// Offsets the indices of a face's two triangles by `start_index`.
+    ///
+    /// Faces are split along the diagonal between their first and third corner. If the other
+    /// diagonal has brighter corners, the face is split along that one instead, so that the
+    /// ambient occlusion of a single dark corner doesn't bleed into both triangles.
+    fn face_indices(indices: [u16; 6], ao: [u8; 4], start_index: u16) -> [u16; 6] {
+        let flip = ao[0] + ao[2] < ao[1] + ao[3];
+
+        let mut face_indices = [0; 6];
+        for (face_index, index) in face_indices.iter_mut().zip(&indices) {
+            let index = if flip { (index + 1) % 4 } else { *index };
+            *face_index = start_index + index;
         }
+        face_indices
     }
 
     /// Converts the quad to `Geometry` (i.e. a list of vertices and indices) to be rendered.
@@ -52,7 +105,7 @@ impl Quad {
     ) -> Geometry<BlockVertex, u16> {
         let dx = self.dx as f32;
         let dz = self.dz as f32;
-        let dy = 1.0;
+        let dy = self.height;
 
         let x = self.position.x as f32;
         let y = self.position.y as f32;
@@ -62,6 +115,19 @@ impl Quad {
             Some(block_type) => (block_type.texture_indices(), block_type.color()),
             None => ((0, 0, 0, 0, 0, 0), Vector4::new(1.0, 1.0, 1.0, 1.0)),
         };
+
+        let shape = self.block_type.map_or(BlockShape::Cube, BlockType::shape);
+        if shape != BlockShape::Cube {
+            return shape.to_geometry(
+                self.facing,
+                self.position,
+                self.visible_faces,
+                t,
+                color,
+                self.light,
+                start_index,
+            );
+        }
         let color = color.into();
 
         let mut current_index = start_index;
@@ -70,103 +136,91 @@ impl Quad {
 
         if self.visible_faces & FACE_LEFT == FACE_LEFT {
             let normal = Vector3::new(-1,  0,  0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
             let normal = normal.cast().unwrap().into();
+            let light = self.light[0].to_vertex();
+            let ao = Self::ao_brightness(self.ao[0]);
             vertices.extend([
-                BlockVertex { position: [x, y,      z     ], texture_coordinates: [dz,  1.0], texture_id: t.0 as i32, normal, highlighted, color },
-                BlockVertex { position: [x, y,      z + dz], texture_coordinates: [0.0, 1.0], texture_id: t.0 as i32, normal, highlighted, color },
-                BlockVertex { position: [x, y + dy, z + dz], texture_coordinates: [0.0, 0.0], texture_id: t.0 as i32, normal, highlighted, color },
-                BlockVertex { position: [x, y + dy, z     ], texture_coordinates: [dz,  0.0], texture_id: t.0 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                2 + current_index, current_index, 1 + current_index,
-                3 + current_index, current_index, 2 + current_index,
+                BlockVertex { position: [x, y,      z     ], texture_coordinates: [dz,  dy ], texture_id: t.0 as i32, normal, color, light, ambient_occlusion: ao[0] },
+                BlockVertex { position: [x, y,      z + dz], texture_coordinates: [0.0, dy ], texture_id: t.0 as i32, normal, color, light, ambient_occlusion: ao[1] },
+                BlockVertex { position: [x, y + dy, z + dz], texture_coordinates: [0.0, 0.0], texture_id: t.0 as i32, normal, color, light, ambient_occlusion: ao[2] },
+                BlockVertex { position: [x, y + dy, z     ], texture_coordinates: [dz,  0.0], texture_id: t.0 as i32, normal, color, light, ambient_occlusion: ao[3] },
             ]);
+            indices.extend(Self::face_indices([2, 0, 1, 3, 0, 2], self.ao[0], current_index));
             current_index += 4;
         }
 
         if self.visible_faces & FACE_RIGHT == FACE_RIGHT {
             let normal = Vector3::new(1, 0, 0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
             let normal = normal.cast().unwrap().into();
+            let light = self.light[1].to_vertex();
+            let ao = Self::ao_brightness(self.ao[1]);
             vertices.extend([
-                BlockVertex { position: [x + dx, y,      z     ], texture_coordinates: [0.0, 1.0], texture_id: t.1 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y,      z + dz], texture_coordinates: [dz,  1.0], texture_id: t.1 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dz,  0.0], texture_id: t.1 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.1 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                1 + current_index, current_index, 2 + current_index,
-                2 + current_index, current_index, 3 + current_index,
+                BlockVertex { position: [x + dx, y,      z     ], texture_coordinates: [0.0, dy ], texture_id: t.1 as i32, normal, color, light, ambient_occlusion: ao[0] },
+                BlockVertex { position: [x + dx, y,      z + dz], texture_coordinates: [dz,  dy ], texture_id: t.1 as i32, normal, color, light, ambient_occlusion: ao[1] },
+                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dz,  0.0], texture_id: t.1 as i32, normal, color, light, ambient_occlusion: ao[2] },
+                BlockVertex { position: [x + dx, y + dy, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.1 as i32, normal, color, light, ambient_occlusion: ao[3] },
             ]);
+            indices.extend(Self::face_indices([1, 0, 2, 2, 0, 3], self.ao[1], current_index));
             current_index += 4;
         }
 
         if self.visible_faces & FACE_BACK == FACE_BACK {
             let normal = Vector3::new(0, 0, -1);
-            let highlighted = (self.highlighted_normal == normal) as i32;
             let normal = normal.cast().unwrap().into();
+            let light = self.light[2].to_vertex();
+            let ao = Self::ao_brightness(self.ao[2]);
             vertices.extend([
-                BlockVertex { position: [x,      y,      z], texture_coordinates: [dx,  1.0], texture_id: t.2 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y + dy, z], texture_coordinates: [dx,  0.0], texture_id: t.2 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z], texture_coordinates: [0.0, 0.0], texture_id: t.2 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y,      z], texture_coordinates: [0.0, 1.0], texture_id: t.2 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                2 + current_index, current_index, 1 + current_index,
-                3 + current_index, current_index, 2 + current_index,
+                BlockVertex { position: [x,      y,      z], texture_coordinates: [dx,  dy ], texture_id: t.2 as i32, normal, color, light, ambient_occlusion: ao[0] },
+                BlockVertex { position: [x,      y + dy, z], texture_coordinates: [dx,  0.0], texture_id: t.2 as i32, normal, color, light, ambient_occlusion: ao[1] },
+                BlockVertex { position: [x + dx, y + dy, z], texture_coordinates: [0.0, 0.0], texture_id: t.2 as i32, normal, color, light, ambient_occlusion: ao[2] },
+                BlockVertex { position: [x + dx, y,      z], texture_coordinates: [0.0, dy ], texture_id: t.2 as i32, normal, color, light, ambient_occlusion: ao[3] },
             ]);
+            indices.extend(Self::face_indices([2, 0, 1, 3, 0, 2], self.ao[2], current_index));
             current_index += 4;
         }
 
         if self.visible_faces & FACE_FRONT == FACE_FRONT {
             let normal = Vector3::new(0, 0, 1);
-            let highlighted = (self.highlighted_normal == normal) as i32;
             let normal = normal.cast().unwrap().into();
+            let light = self.light[3].to_vertex();
+            let ao = Self::ao_brightness(self.ao[3]);
             vertices.extend([
-                BlockVertex { position: [x,      y,      z + dz], texture_coordinates: [0.0, 1.0], texture_id: t.3 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y + dy, z + dz], texture_coordinates: [0.0, 0.0], texture_id: t.3 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dx,  0.0], texture_id: t.3 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y,      z + dz], texture_coordinates: [dx,  1.0], texture_id: t.3 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                1 + current_index, current_index, 2 + current_index,
-                2 + current_index, current_index, 3 + current_index,
+                BlockVertex { position: [x,      y,      z + dz], texture_coordinates: [0.0, dy ], texture_id: t.3 as i32, normal, color, light, ambient_occlusion: ao[0] },
+                BlockVertex { position: [x,      y + dy, z + dz], texture_coordinates: [0.0, 0.0], texture_id: t.3 as i32, normal, color, light, ambient_occlusion: ao[1] },
+                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dx,  0.0], texture_id: t.3 as i32, normal, color, light, ambient_occlusion: ao[2] },
+                BlockVertex { position: [x + dx, y,      z + dz], texture_coordinates: [dx,  dy ], texture_id: t.3 as i32, normal, color, light, ambient_occlusion: ao[3] },
             ]);
+            indices.extend(Self::face_indices([1, 0, 2, 2, 0, 3], self.ao[3], current_index));
             current_index += 4;
         }
 
         if self.visible_faces & FACE_BOTTOM == FACE_BOTTOM {
             let normal = Vector3::new(0, -1, 0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
             let normal = normal.cast().unwrap().into();
+            let light = self.light[4].to_vertex();
+            let ao = Self::ao_brightness(self.ao[4]);
             vertices.extend([
-                BlockVertex { position: [x,      y, z     ], texture_coordinates: [dx,  0.0], texture_id: t.4 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y, z + dz], texture_coordinates: [dx,  dz ], texture_id: t.4 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y, z + dz], texture_coordinates: [0.0, dz ], texture_id: t.4 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.4 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                current_index, 2 + current_index, 1 + current_index,
-                current_index, 3 + current_index, 2 + current_index,
+                BlockVertex { position: [x,      y, z     ], texture_coordinates: [dx,  0.0], texture_id: t.4 as i32, normal, color, light, ambient_occlusion: ao[0] },
+                BlockVertex { position: [x,      y, z + dz], texture_coordinates: [dx,  dz ], texture_id: t.4 as i32, normal, color, light, ambient_occlusion: ao[1] },
+                BlockVertex { position: [x + dx, y, z + dz], texture_coordinates: [0.0, dz ], texture_id: t.4 as i32, normal, color, light, ambient_occlusion: ao[2] },
+                BlockVertex { position: [x + dx, y, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.4 as i32, normal, color, light, ambient_occlusion: ao[3] },
             ]);
+            indices.extend(Self::face_indices([0, 2, 1, 0, 3, 2], self.ao[4], current_index));
             current_index += 4;
         }
 
         if self.visible_faces & FACE_TOP == FACE_TOP {
             let normal = Vector3::new(0, 1, 0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
             let normal = normal.cast().unwrap().into();
+            let light = self.light[5].to_vertex();
+            let ao = Self::ao_brightness(self.ao[5]);
             vertices.extend([
-                BlockVertex { position: [x,      y + dy, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.5 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y + dy, z + dz], texture_coordinates: [0.0, dz ], texture_id: t.5 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dx,  dz ], texture_id: t.5 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z     ], texture_coordinates: [dx,  0.0], texture_id: t.5 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                current_index, 1 + current_index, 2 + current_index,
-                current_index, 2 + current_index, 3 + current_index,
+                BlockVertex { position: [x,      y + dy, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.5 as i32, normal, color, light, ambient_occlusion: ao[0] },
+                BlockVertex { position: [x,      y + dy, z + dz], texture_coordinates: [0.0, dz ], texture_id: t.5 as i32, normal, color, light, ambient_occlusion: ao[1] },
+                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dx,  dz ], texture_id: t.5 as i32, normal, color, light, ambient_occlusion: ao[2] },
+                BlockVertex { position: [x + dx, y + dy, z     ], texture_coordinates: [dx,  0.0], texture_id: t.5 as i32, normal, color, light, ambient_occlusion: ao[3] },
             ]);
+            indices.extend(Self::face_indices([0, 1, 2, 0, 2, 3], self.ao[5], current_index));
         }
 
         Geometry::new(vertices, indices)
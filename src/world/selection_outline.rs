@@ -0,0 +1,149 @@
+use cgmath::{EuclideanSpace, Point3, Vector3};
+use wgpu::{BufferUsage, RenderPass, RenderPipeline};
+
+use crate::{
+    geometry::Geometry,
+    geometry_buffers::GeometryBuffers,
+    render_context::RenderContext,
+    texture::Texture,
+    vertex::{OutlineVertex, Vertex},
+    view::View,
+    world::block::Block,
+};
+
+/// How far the outline is drawn outside of the block, so it doesn't z-fight with its edges.
+const OUTLINE_OFFSET: f32 = 0.002;
+
+/// The indices of the corners of a box that make up its 12 edges, with the corners ordered like
+/// the bits of their index (X, Y, Z).
+#[rustfmt::skip]
+const BOX_EDGES: [u16; 24] = [
+    0, 1, 2, 3, 4, 5, 6, 7, // Along the X axis
+    0, 2, 1, 3, 4, 6, 5, 7, // Along the Y axis
+    0, 4, 1, 5, 2, 6, 3, 7, // Along the Z axis
+];
+
+/// The wireframe drawn around the block the player is pointing at.
+pub struct SelectionOutline {
+    render_pipeline: RenderPipeline,
+    /// The block the outline is currently drawn around.
+    selected: Option<(Point3<isize>, Block)>,
+    buffers: Option<GeometryBuffers<u16>>,
+}
+
+impl SelectionOutline {
+    pub fn new(render_context: &RenderContext, view: &View) -> Self {
+        let pipeline_layout =
+            render_context
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("outline_pipeline_layout"),
+                    push_constant_ranges: &[],
+                    bind_group_layouts: &[&view.bind_group_layout],
+                });
+
+        let shader = render_context.device.create_shader_module(
+            &(wgpu::ShaderModuleDescriptor {
+                label: Some("outline_shader"),
+                flags: wgpu::ShaderFlags::all(),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/outline.wgsl").into()),
+            }),
+        );
+
+        let render_pipeline =
+            render_context
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Outline Render Pipeline"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "main",
+                        buffers: &[OutlineVertex::descriptor()],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "main",
+                        targets: &[wgpu::ColorTargetState {
+                            format: render_context.swap_chain_descriptor.format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrite::ALL,
+                        }],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::LineList,
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: Texture::DEPTH_FORMAT,
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Greater,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                });
+
+        Self {
+            render_pipeline,
+            selected: None,
+            buffers: None,
+        }
+    }
+
+    /// Moves the outline to the block at the given position, or hides it if `None`.
+    pub fn update(
+        &mut self,
+        render_context: &RenderContext,
+        selected: Option<(Point3<isize>, Block)>,
+    ) {
+        if selected == self.selected {
+            return;
+        }
+
+        self.selected = selected;
+        self.buffers = selected.map(|(position, block)| {
+            GeometryBuffers::from_geometry(
+                render_context,
+                &Self::outline_geometry(position, &block),
+                BufferUsage::empty(),
+            )
+        });
+    }
+
+    /// Creates the edges of every box of the block's shape.
+    fn outline_geometry(position: Point3<isize>, block: &Block) -> Geometry<OutlineVertex, u16> {
+        let offset = position.cast::<f32>().unwrap();
+        let mut geometry = Geometry::default();
+        for aabb in block.block_type.shape().boxes(block.facing) {
+            let min = offset + aabb.min.to_vec().map(|x| x - OUTLINE_OFFSET);
+            let max = offset + aabb.max.to_vec().map(|x| x + OUTLINE_OFFSET);
+
+            let start_index = geometry.vertices.len() as u16;
+            let vertices = (0..8)
+                .map(|corner| {
+                    let corner = Vector3::new(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+                    OutlineVertex {
+                        position: [
+                            if corner.x == 0 { min.x } else { max.x },
+                            if corner.y == 0 { min.y } else { max.y },
+                            if corner.z == 0 { min.z } else { max.z },
+                        ],
+                    }
+                })
+                .collect();
+            let indices = BOX_EDGES.iter().map(|i| start_index + i).collect();
+            geometry.append(&mut Geometry::new(vertices, indices));
+        }
+        geometry
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, view: &'a View) {
+        if let Some(buffers) = &self.buffers {
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, &view.bind_group, &[]);
+            buffers.apply_buffers(render_pass);
+            buffers.draw_indexed(render_pass);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use cgmath::Point3;
+use fxhash::FxHashSet;
+
+/// Time between two world ticks.
+pub const TICK: Duration = Duration::from_millis(50);
+
+/// The maximum number of ticks run in a single frame when catching up after a long frame.
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+/// Runs world ticks at a fixed rate independent of the framerate, and keeps track of the blocks
+/// that are scheduled to be ticked.
+#[derive(Default)]
+pub struct TickScheduler {
+    /// The number of ticks that have passed.
+    pub tick: u64,
+    timer: Duration,
+    scheduled: BTreeMap<u64, FxHashSet<Point3<isize>>>,
+}
+
+impl TickScheduler {
+    /// Advances the timer by `dt`, and returns the number of ticks that have to be run.
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        self.timer += dt;
+
+        let mut ticks = 0;
+        while self.timer >= TICK && ticks < MAX_TICKS_PER_FRAME {
+            self.timer -= TICK;
+            ticks += 1;
+        }
+
+        // Don't try to catch up on missed ticks after a very long frame
+        self.timer = self.timer.min(TICK);
+        ticks
+    }
+
+    /// Schedules the block at `position` to be ticked `delay` ticks from now.
+    pub fn schedule(&mut self, position: Point3<isize>, delay: u64) {
+        self.scheduled
+            .entry(self.tick + delay.max(1))
+            .or_default()
+            .insert(position);
+    }
+
+    /// Moves on to the next tick, and returns the blocks that are scheduled to be ticked in it.
+    pub fn next_tick(&mut self) -> FxHashSet<Point3<isize>> {
+        self.tick += 1;
+        self.scheduled.remove(&self.tick).unwrap_or_default()
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    settings::Settings,
+    world::{
+        block::{Block, RenderClass},
+        block_shape::BlockShape,
+        chunk::{Chunk, CHUNK_SIZE},
+    },
+};
+
+/// Which faces of a chunk can see each other through it, for culling chunks that are hidden
+/// behind others, e.g. in caves or behind mountains.
+///
+/// Two faces are connected if a path of blocks that can be seen through leads from one to the
+/// other. The faces are indexed in the same order as `NEIGHBOURS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkVisibility {
+    /// Bit `from * 6 + to` is set if the faces `from` and `to` are connected.
+    connections: u64,
+}
+
+impl Default for ChunkVisibility {
+    /// Every face is connected, so chunks that haven't been meshed yet don't hide anything.
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl ChunkVisibility {
+    pub const NONE: Self = Self { connections: 0 };
+    pub const ALL: Self = Self {
+        connections: (1 << 36) - 1,
+    };
+
+    /// Returns whether the faces `from` and `to` can see each other.
+    pub fn is_connected(self, from: usize, to: usize) -> bool {
+        (self.connections >> (from * 6 + to)) & 1 == 1
+    }
+
+    /// Connects all faces in `faces`, a mask with bit `i` set for face `i`, to each other.
+    fn connect(&mut self, faces: u8) {
+        for from in (0..6).filter(|from| (faces >> from) & 1 == 1) {
+            for to in (0..6).filter(|to| (faces >> to) & 1 == 1) {
+                self.connections |= 1 << (from * 6 + to);
+            }
+        }
+    }
+
+    /// Finds which faces of the chunk are connected, by flood filling the blocks that can be seen
+    /// through from every face.
+    pub fn compute(chunk: &Chunk, settings: &Settings) -> Self {
+        let index = |x: usize, y: usize, z: usize| (y * CHUNK_SIZE + z) * CHUNK_SIZE + x;
+        let mut visited = vec![false; CHUNK_SIZE.pow(3)];
+        for (y, layer) in chunk.blocks.iter().enumerate() {
+            for (z, row) in layer.iter().enumerate() {
+                for (x, block) in row.iter().enumerate() {
+                    if !is_see_through(*block, settings) {
+                        visited[index(x, y, z)] = true;
+                    }
+                }
+            }
+        }
+
+        let last = CHUNK_SIZE - 1;
+        let mut visibility = Self::NONE;
+        let mut stack = Vec::new();
+        for (x, y, z) in itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+            // Only the blocks that make up a face can start a path between two faces
+            let on_face = [x, y, z].iter().any(|&n| n == 0 || n == last);
+            if !on_face || visited[index(x, y, z)] {
+                continue;
+            }
+
+            let mut faces = 0u8;
+            visited[index(x, y, z)] = true;
+            stack.push((x, y, z));
+            while let Some((x, y, z)) = stack.pop() {
+                faces |= (x == 0) as u8
+                    | ((x == last) as u8) << 1
+                    | ((z == 0) as u8) << 2
+                    | ((z == last) as u8) << 3
+                    | ((y == 0) as u8) << 4
+                    | ((y == last) as u8) << 5;
+
+                let mut visit = |x: usize, y: usize, z: usize| {
+                    if !visited[index(x, y, z)] {
+                        visited[index(x, y, z)] = true;
+                        stack.push((x, y, z));
+                    }
+                };
+                if x > 0 {
+                    visit(x - 1, y, z);
+                }
+                if x < last {
+                    visit(x + 1, y, z);
+                }
+                if z > 0 {
+                    visit(x, y, z - 1);
+                }
+                if z < last {
+                    visit(x, y, z + 1);
+                }
+                if y > 0 {
+                    visit(x, y - 1, z);
+                }
+                if y < last {
+                    visit(x, y + 1, z);
+                }
+            }
+
+            visibility.connect(faces);
+        }
+
+        visibility
+    }
+}
+
+/// Returns whether the block doesn't fully hide what's behind it. Cutout and translucent blocks,
+/// like leaves and water, can always be seen through.
+fn is_see_through(block: Option<Block>, settings: &Settings) -> bool {
+    match block {
+        Some(block) => {
+            block.block_type.shape() != BlockShape::Cube
+                || block.block_type.render_class(settings) != RenderClass::Opaque
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::BlockType;
+
+    #[test]
+    fn wall_separates_faces() {
+        let mut chunk = Chunk::default();
+        for layer in chunk.blocks.iter_mut() {
+            for row in layer.iter_mut() {
+                row[16] = Some(Block::new(BlockType::Stone));
+            }
+        }
+
+        let visibility = ChunkVisibility::compute(&chunk, &Settings::default());
+        assert!(!visibility.is_connected(0, 1));
+        assert!(visibility.is_connected(0, 2));
+        assert!(visibility.is_connected(1, 5));
+        assert!(visibility.is_connected(2, 3));
+    }
+
+    #[test]
+    fn solid_chunk_connects_nothing() {
+        let chunk = Chunk {
+            blocks: [[[Some(Block::new(BlockType::Stone)); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            ..Chunk::default()
+        };
+
+        let visibility = ChunkVisibility::compute(&chunk, &Settings::default());
+        assert_eq!(visibility, ChunkVisibility::NONE);
+    }
+
+    #[test]
+    fn water_chunk_connects_everything() {
+        let chunk = Chunk {
+            blocks: [[[Some(Block::new(BlockType::Water)); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            ..Chunk::default()
+        };
+
+        let visibility = ChunkVisibility::compute(&chunk, &Settings::default());
+        assert_eq!(visibility, ChunkVisibility::ALL);
+    }
+}